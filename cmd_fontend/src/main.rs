@@ -3,10 +3,21 @@ extern crate image;
 extern crate core;
 
 use image::load;
-use computer_vision::cpu::{CpuGenerator, CpuPipeline, Image};
-use computer_vision::Filter;
+use computer_vision::backend::Backend;
+use computer_vision::cpu::{CpuGenerator, CpuPipeline, Image, PipelineProfile};
+use computer_vision::{BorderMode, DitherMethod, Filter, FrequencyResponse, Interpolation, OutputSize, Shrinkage};
+use computer_vision::exif::read_orientation;
+use computer_vision::export::{edges_to_svg, to_csv, to_geojson, trace_polylines};
+use computer_vision::kernel::Kernel;
+use computer_vision::labels::LabelImage;
+use computer_vision::lsd::lsd;
+use computer_vision::mask::Mask;
+use computer_vision::metrics::{focus_measure, mse, psnr, ssim};
 use computer_vision::pipeline::{Generator, Pipeline};
 
+/// Side of the window SSIM is computed over when comparing to `--reference`
+const SSIM_WINDOW: usize = 7;
+
 trait ParseArgs {
     fn parse(self, s: String, img: &Image) -> Self;
 }
@@ -31,20 +42,142 @@ impl ParseArgs for CpuPipeline {
                 let size: usize = opt.next()
                     .expect("Expected size of blur")
                     .parse()
-                    .expect("Invalid gaussian blur size");
-                let size = size + size % 2;
-                self.filter(CpuGenerator::new(size)
-                    .average_needle())
+                    .expect("Invalid average blur size");
+                self.average_blur(size)
+            },
+
+            "--disk-blur" => {
+                let radius = opt.next()
+                    .expect("Expected radius of blur")
+                    .parse()
+                    .expect("Invalid disk blur radius");
+                self.filter(CpuGenerator::new(1).disk_needle(radius))
+            },
+
+            "--kernel" => {
+                let rows = opt.next()
+                    .expect("Expected kernel rows, such as 0,-1,0/-1,5,-1/0,-1,0")
+                    .split("/")
+                    .map(|row| row.split(",")
+                        .map(|x| x.parse().expect(&format!("Invalid weight {x}")))
+                        .collect())
+                    .collect();
+                self.filter(Filter::Custom(rows))
+            },
+
+            "--kernel-file" => {
+                let path = opt.next()
+                    .expect("Expected path of a CSV or JSON kernel");
+                let kernel = Kernel::load(path)
+                    .unwrap_or_else(|err| panic!("{}", err));
+                self.filter(kernel.into())
+            },
+
+            "--border" => match opt.next().expect("Expected border mode") {
+                "replicate" => self.border(BorderMode::Replicate),
+                "reflect" => self.border(BorderMode::Reflect),
+                "wrap" => self.border(BorderMode::Wrap),
+                mode => panic!("Unknown border mode '{}'", mode),
+            },
+
+            "--output-size" => match opt.next().expect("Expected output size") {
+                "same" => self.output_size(OutputSize::Same),
+                "valid" => self.output_size(OutputSize::Valid),
+                "full" => self.output_size(OutputSize::Full),
+                mode => panic!("Unknown output size '{}'", mode),
+            },
+
+            "--lowpass" | "--highpass" => {
+                let args: Vec<&str> = opt.next()
+                    .expect("Expected cutoff frequency")
+                    .split(",")
+                    .collect();
+                let cutoff = args[0].parse()
+                    .expect("Invalid cutoff frequency");
+                // Gaussian unless given a Butterworth order
+                let response = match args.get(1) {
+                    Some(order) => FrequencyResponse::Butterworth(order.parse()
+                        .expect("Invalid Butterworth order")),
+                    None => FrequencyResponse::Gaussian,
+                };
+                if command == "--lowpass" {
+                    self.freq_lowpass(response, cutoff)
+                } else {
+                    self.freq_highpass(response, cutoff)
+                }
+            },
+
+            "--notch" => {
+                let args: Vec<f64> = opt.next()
+                    .expect("Expected notch frequency")
+                    .split(",")
+                    .map(|x| x.parse().expect(&format!("Invalid notch argument {x}")))
+                    .collect();
+                match args[..] {
+                    [u, v] => self.notch_reject(vec![(u, v)], 0.02),
+                    [u, v, radius] => self.notch_reject(vec![(u, v)], radius),
+                    _ => panic!("Expected notch frequency as u,v or u,v,radius")
+                }
+            },
+
+            "--wavelet-denoise" => {
+                let args: Vec<&str> = opt.next()
+                    .expect("Expected wavelet levels and threshold")
+                    .split(",")
+                    .collect();
+                let shrinkage = match args.get(2) {
+                    None | Some(&"soft") => Shrinkage::Soft,
+                    Some(&"hard") => Shrinkage::Hard,
+                    Some(x) => panic!("Unknown shrinkage {x}, expected soft or hard"),
+                };
+                match args[..] {
+                    [levels, threshold, ..] => self.wavelet_denoise(
+                        levels.parse().expect(&format!("Invalid wavelet levels {levels}")),
+                        threshold.parse().expect(&format!("Invalid wavelet threshold {threshold}")),
+                        shrinkage),
+                    _ => panic!("Expected wavelet denoising as levels,threshold[,soft|hard]")
+                }
+            },
+
+            "--resize" => {
+                let args: Vec<&str> = opt.next()
+                    .expect("Expected size as WIDTHxHEIGHT")
+                    .split(",")
+                    .collect();
+                let (width, height) = args[0].split_once('x')
+                    .expect("Expected size as WIDTHxHEIGHT");
+                let interpolation = match args.get(1) {
+                    None | Some(&"bilinear") => Interpolation::Bilinear,
+                    Some(&"bicubic") => Interpolation::Bicubic,
+                    Some(&"nearest") => Interpolation::Nearest,
+                    Some(x) => panic!("Unknown interpolation {x}, expected nearest, bilinear or bicubic"),
+                };
+                self.resize(
+                    width.parse().expect(&format!("Invalid width {width}")),
+                    height.parse().expect(&format!("Invalid height {height}")),
+                    interpolation)
             },
 
             "--median" => {
                 let size = opt.next()
-                    .expect("Expected size of blur")
+                    .expect("Expected median size")
                     .parse()
-                    .expect("Invalid gaussian blur size");
+                    .expect("Invalid median size");
                 self.filter(Filter::Median(size))
             },
 
+            "--bilateral" => {
+                let sigmas: Vec<f64> = opt.next()
+                    .unwrap_or("2,0.1")
+                    .split(",")
+                    .map(|x| x.parse().expect(&format!("Invalid sigma {x}")))
+                    .collect();
+                match sigmas[..] {
+                    [spatial, range] => self.bilateral(spatial, range),
+                    _ => panic!("Expected spatial and range sigmas")
+                }
+            },
+
             "--gaussian-noise" => {
                 let variance: f64 = opt.next()
                     .expect("Expected variance of noise")
@@ -67,21 +200,110 @@ impl ParseArgs for CpuPipeline {
 
             "--canny" => {
                 let threshold: Vec<f64> = opt.next()
-                    .unwrap_or("0.0")
+                    .unwrap_or("0.01,0.03")
                     .split(",")
                     .map(|x| x.parse().expect(&format!("Invalid threshold {x}")))
                     .collect();
-                self.canny(threshold)
+                match threshold[..] {
+                    [low, high] => self.canny(low, high),
+                    _ => panic!("Expected low and high thresholds")
+                }
+            },
+
+            "--auto-canny" => self.auto_canny(),
+            "--edge-strength" => self.canny_strength(),
+
+            "--posterize" => {
+                let levels = opt.next()
+                    .expect("Expected levels per channel")
+                    .parse()
+                    .expect("Invalid posterize levels");
+                self.posterize(levels)
+            },
+
+            "--colors" => {
+                let colors = opt.next()
+                    .expect("Expected number of colours")
+                    .parse()
+                    .expect("Invalid number of colours");
+                self.quantize_colors(colors)
+            },
+
+            "--dither" => {
+                let levels = opt.next()
+                    .unwrap_or("2")
+                    .parse()
+                    .expect("Invalid dither levels");
+                self.dither(levels, DitherMethod::FloydSteinberg)
+            },
+
+            "--solarize" => {
+                let threshold = opt.next()
+                    .unwrap_or("0.5")
+                    .parse()
+                    .expect("Invalid solarize threshold");
+                self.solarize(threshold)
+            },
+
+            "--gamma" => {
+                let gamma = opt.next()
+                    .expect("Expected gamma")
+                    .parse()
+                    .expect("Invalid gamma");
+                self.gamma(gamma)
+            },
+
+            "--decimate" => {
+                let factor = opt.next()
+                    .unwrap_or("2")
+                    .parse()
+                    .expect("Invalid decimation factor");
+                self.decimate(factor)
             },
 
             "--grayscale" => self.grayscale(),
+            "--sepia" => self.sepia(),
             "--gradient" => self.gradient(),
+            "--otsu" => self.threshold_otsu(),
+            "--equalize" => self.equalize_hist(),
+            "--normalize" => self.normalize(),
 
             unknown => panic!("Unexpected option '{}'", unknown)
         }
     }
 }
 
+/// Line segments, connected components of the pixels brighter than a half,
+/// and a few metrics of the result as a JSON object. `quality` is the MSE,
+/// PSNR and SSIM against the reference, if any. `corners` is always empty,
+/// as there is no corner detector yet
+fn results_json(data: &Image, profile: &PipelineProfile, quality: Option<(f64, f64, f64)>) -> String {
+    let components = LabelImage::connected_components(data, 0.5).regionprops();
+    // JSON has no infinity, which is the PSNR of identical images
+    let quality = quality.map_or(String::new(), |(mse, psnr, ssim)| format!(
+        ",\"mse\":{mse},\"psnr\":{},\"ssim\":{ssim}",
+        if psnr.is_finite() { psnr.to_string() } else { "null".to_string() }
+    ));
+    format!(
+        "{{\"width\":{},\"height\":{},\"milliseconds\":{},\"set_pixels\":{},\"lines\":{},\"corners\":[],\"components\":{}{quality}}}",
+        data.width(),
+        data.height(),
+        profile.total_duration().as_secs_f64() * 1000.0,
+        Mask::threshold(data, 0.5).count(),
+        to_geojson(&lsd(data)).trim_end(),
+        to_geojson(&components).trim_end(),
+    )
+}
+
+fn load_image(uri: &str) -> Image {
+    image::io::Reader::open(uri)
+        .expect(&format!("Unable to load image '{}'", uri))
+        .decode()
+        .unwrap()
+        .into_rgba8()
+        .into()
+}
+
 fn main() {
     let mut args = std::env::args();
     args.next().unwrap();
@@ -90,25 +312,67 @@ fn main() {
         .expect("Expected source image");
 
     let dest_uri = args.next()
-        .expect("Expected destination image");
+        .expect("Expected destination image, or - to only print --json results");
 
-    println!("Loading image {}", src_uri);
+    // Only the CPU backend exists for now, but reject unknown names early
+    let backend = match std::env::var("CANNY_BACKEND") {
+        Ok(name) => Backend::by_name(&name)
+            .expect(&format!("Unknown backend '{}'", name)),
+        Err(_) => Backend::default(),
+    };
+    eprintln!("Using backend {}", backend.name);
 
-    let surface = image::io::Reader::open(&src_uri)
-        .expect(&format!("Unable to load image '{}'", src_uri))
-        .decode()
-        .unwrap()
-        .into_rgba8()
-        .into();
+    eprintln!("Loading image {}", src_uri);
+
+    let surface = load_image(&src_uri);
+
+    let orientation = read_orientation(&src_uri)
+        .unwrap_or_default();
 
-    let pipeline = args.fold(
-        CpuPipeline::default(),
+    let (json, actions): (Vec<_>, Vec<_>) = args.partition(|arg| arg == "--json");
+    let json = !json.is_empty();
+    // `--reference` compares the result to the source, `--reference=path` to another image
+    let (reference, actions): (Vec<_>, Vec<_>) = actions.into_iter()
+        .partition(|arg| arg == "--reference" || arg.starts_with("--reference="));
+
+    // Skips blurry frames, with a non-zero status so batch jobs can tell
+    let (min_focus, actions): (Vec<_>, Vec<_>) = actions.into_iter()
+        .partition(|arg| arg.starts_with("--min-focus="));
+    if let Some(arg) = min_focus.last() {
+        let threshold: f64 = arg["--min-focus=".len()..].parse()
+            .expect("Invalid focus threshold");
+        let focus = focus_measure(&surface);
+        if focus < threshold {
+            eprintln!("Rejected: focus {focus:.6} is below {threshold}");
+            std::process::exit(2);
+        }
+    }
+
+    let pipeline = actions.into_iter().fold(
+        CpuPipeline::default().orient(orientation),
         |pipeline, action| pipeline.parse(action, &surface)
     );
 
-    println!("Calculating");
-    let data = pipeline.apply(&surface);
-    println!("Calculated: {}x{}", data.width(), data.height());
+    eprintln!("Calculating");
+    let (data, profile) = pipeline.apply_profiled(&surface);
+    eprintln!("Calculated: {}x{}", data.width(), data.height());
+
+    let quality = reference.last().map(|arg| {
+        let reference = match arg.split_once('=') {
+            Some((_, uri)) => load_image(uri),
+            None => CpuPipeline::default().orient(orientation).apply(&surface),
+        };
+        (mse(&data, &reference), psnr(&data, &reference), ssim(&data, &reference, SSIM_WINDOW))
+    });
+
+    if json {
+        println!("{}", results_json(&data, &profile, quality));
+    } else if let Some((mse, psnr, ssim)) = quality {
+        println!("MSE {mse:.6}, PSNR {psnr:.2} dB, SSIM {ssim:.4}");
+    }
+    if dest_uri == "-" {
+        return;
+    }
 
     let dir = std::env::current_dir().map(|mut dir| {
         dir.push(dest_uri);
@@ -116,5 +380,10 @@ fn main() {
     })
         .expect("Unable to open directory");
 
-    data.save(dir.clone()).unwrap();
+    match dir.extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => std::fs::write(&dir, edges_to_svg(&data)).unwrap(),
+        Some("geojson") => std::fs::write(&dir, to_geojson(&trace_polylines(&data, 0.5))).unwrap(),
+        Some("csv") => std::fs::write(&dir, to_csv(&trace_polylines(&data, 0.5))).unwrap(),
+        _ => data.save(dir.clone()).unwrap(),
+    }
 }
@@ -0,0 +1,112 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use gtk::prelude::*;
+use gtk::Widget;
+use computer_vision::histogram::Histogram;
+use crate::util::Event;
+
+/// Bins of the gradient magnitude histogram shown in the panel
+pub const HISTOGRAM_BINS: usize = 128;
+
+/// Draws a histogram of values in [0, 1] with a low and a high threshold
+/// marker, which the user drags along it
+#[derive(Clone)]
+pub struct HistogramPanel {
+    area: gtk::DrawingArea,
+    histogram: Rc<RefCell<Option<Histogram>>>,
+    markers: Rc<Cell<(f64, f64)>>,
+    changed: Event<(f64, f64)>,
+}
+
+impl HistogramPanel {
+    pub fn new() -> HistogramPanel {
+        let panel = HistogramPanel {
+            area: gtk::DrawingArea::builder()
+                .height_request(80)
+                .hexpand(true)
+                .build(),
+            histogram: Rc::new(RefCell::new(None)),
+            markers: Rc::new(Cell::new((0.01, 0.03))),
+            changed: Event::new(),
+        };
+
+        let histogram = panel.histogram.clone();
+        let markers = panel.markers.clone();
+        panel.area.set_draw_func(move |_, cr, width, height| {
+            let (width, height) = (width as f64, height as f64);
+            cr.set_source_rgb(0.15, 0.15, 0.15);
+            cr.paint().ok();
+
+            if let Some(histogram) = &*histogram.borrow() {
+                // Log counts, or the peak of weak gradients flattens the rest
+                let bins = histogram.bins();
+                let peak = bins.iter().map(|&c| (c as f64).ln_1p()).fold(0.0, f64::max).max(1.0);
+                let bar = width / bins.len() as f64;
+                cr.set_source_rgb(0.7, 0.7, 0.7);
+                for (n, &count) in bins.iter().enumerate() {
+                    let h = (count as f64).ln_1p() / peak * height;
+                    cr.rectangle(n as f64 * bar, height - h, bar, h);
+                }
+                cr.fill().ok();
+            }
+
+            let (low, high) = markers.get();
+            for (value, (r, g, b)) in [(low, (0.3, 0.6, 1.0)), (high, (1.0, 0.4, 0.3))] {
+                cr.set_source_rgb(r, g, b);
+                cr.rectangle(value * width - 1.0, 0.0, 2.0, height);
+                cr.fill().ok();
+            }
+        });
+
+        // Index of the marker being dragged, the one nearest to where the drag began
+        let dragged = Rc::new(Cell::new(0usize));
+        let drag = gtk::GestureDrag::new();
+        let (this, marker) = (panel.clone(), dragged.clone());
+        drag.connect_drag_begin(move |_, x, _| {
+            let x = this.fraction(x);
+            let (low, high) = this.markers.get();
+            marker.set(((x - low).abs() > (x - high).abs()) as usize);
+            this.move_marker(marker.get(), x);
+        });
+        let this = panel.clone();
+        drag.connect_drag_update(move |gesture, dx, _| {
+            if let Some((x, _)) = gesture.start_point() {
+                this.move_marker(dragged.get(), this.fraction(x + dx));
+            }
+        });
+        panel.area.add_controller(&drag);
+
+        panel
+    }
+
+    /// A horizontal position as a fraction of the width
+    fn fraction(&self, x: f64) -> f64 {
+        x / self.area.width().max(1) as f64
+    }
+
+    fn move_marker(&self, marker: usize, value: f64) {
+        let value = value.clamp(0.0, 1.0);
+        let (low, high) = self.markers.get();
+        let markers = match marker {
+            0 => (value.min(high), high),
+            _ => (low, value.max(low)),
+        };
+        self.markers.set(markers);
+        self.area.queue_draw();
+        (self.changed)(markers.0, markers.1);
+    }
+
+    pub fn set_histogram(&self, histogram: Histogram) {
+        *self.histogram.borrow_mut() = Some(histogram);
+        self.area.queue_draw();
+    }
+
+    /// Called with the low and high thresholds whenever a marker moves
+    pub fn connect_thresholds(&self, f: impl Fn(f64, f64) + 'static) {
+        self.changed.connect(f);
+    }
+
+    pub fn as_widget(&self) -> impl IsA<Widget> {
+        self.area.clone()
+    }
+}
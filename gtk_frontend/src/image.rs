@@ -3,12 +3,17 @@ use std::io::Cursor;
 use std::path::Path;
 use std::sync::{Arc, RwLock, Weak};
 use std::thread;
+use std::time::Duration;
 use gdk_pixbuf::glib::{Bytes, MainContext};
 use gdk_pixbuf::glib::clone::{Downgrade, Upgrade};
 use gtk::glib::{Cast, PRIORITY_DEFAULT, WeakRef};
 use computer_vision::cpu::{CpuGenerator, CpuPipeline, Image as RgbaImage};
+use computer_vision::exif::read_orientation;
+use computer_vision::histogram::Histogram;
 use computer_vision::pipeline::{Generator, Pipeline};
 use crate::{AddableAt, Continue, IsA, With};
+use crate::histogram::HISTOGRAM_BINS;
+use crate::util::Event;
 
 #[derive(Copy, Clone, Debug)]
 pub struct GaussianCoeff {
@@ -16,16 +21,29 @@ pub struct GaussianCoeff {
     pub variance: f64
 }
 
+/// Adds an operation's stages to a pipeline, given the image it runs on
+type Stage = Arc<dyn Fn(CpuPipeline, &RgbaImage) -> CpuPipeline + Send + Sync>;
+
+/// An operation applied to the image, which can be replayed on another
+#[derive(Clone)]
+pub struct LogEntry {
+    pub operation: String,
+    pub duration: Duration,
+    stage: Stage,
+}
+
 #[derive(Clone)]
 pub struct Image {
     pixbuf: Arc<RwLock<RgbaImage>>,
     stack: gtk::Stack,
+    logged: Event<(LogEntry,)>,
 }
 
 #[derive(Clone)]
 pub struct WeakImage {
     pixbuf: Weak<RwLock<RgbaImage>>,
     stack: WeakRef<gtk::Stack>,
+    logged: Event<(LogEntry,)>,
 }
 
 impl Image {
@@ -54,7 +72,8 @@ impl Image {
 
                 Image {
                     pixbuf: Arc::new(RwLock::new(RgbaImage::empty(0, 0))),
-                    stack: w
+                    stack: w,
+                    logged: Event::new(),
                 }
             });
         this
@@ -68,7 +87,10 @@ impl Image {
             ) {
             Ok(img) => {
                 println!("Setting image to {}", file.display());
-                *self.pixbuf.write().unwrap() = img.into_rgba8().into();
+                let orientation = read_orientation(file)
+                    .unwrap_or_default();
+                *self.pixbuf.write().unwrap() = RgbaImage::from(img.into_rgba8())
+                    .oriented(orientation);
                 self.stack.child_by_name("image")
                     .unwrap()
                     .dynamic_cast::<gtk::Picture>()
@@ -84,7 +106,11 @@ impl Image {
         self.stack.clone()
     }
 
-    fn calculate(&self, f: impl FnOnce(&RgbaImage) -> RgbaImage + 'static + Send) {
+    /// Runs `f` in the background and shows the image it returns, then hands
+    /// the rest of its result to `done` on the main loop
+    fn calculate<T: Send + 'static>(&self,
+                                    f: impl FnOnce(&RgbaImage) -> (RgbaImage, T) + 'static + Send,
+                                    done: impl FnOnce(T) + 'static) {
         let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
         let pixbuf = self.pixbuf.clone();
 
@@ -93,7 +119,7 @@ impl Image {
         thread::spawn(move || {
             println!("Calculating");
             let surface = pixbuf.read().unwrap();
-            let data = f(&*surface);
+            let (data, result) = f(&*surface);
             println!("Calculated: {}x{}", data.width(), data.height());
             let dir = std::env::temp_dir().with(|mut dir| {
                 dir.push("img.png");
@@ -101,21 +127,87 @@ impl Image {
             });
             data.save(dir.clone()).unwrap();
             sender.send(
-                dir
+                (dir, result)
             ).expect("Could not send through channel");
         });
 
         let weak_self = self.downgrade();
+        let mut done = Some(done);
 
         receiver.attach(
             None,
-            move |new_image| {
+            move |(new_image, result)| {
                 let this = weak_self.upgrade().unwrap();
 
                 this.set_new(&new_image);
 
                 this.stack.set_visible_child_name("image");
 
+                if let Some(done) = done.take() {
+                    done(result);
+                }
+
+                Continue(false)
+            }
+        );
+    }
+
+    /// Applies an operation and adds it, with its duration, to the log
+    fn run(&self, operation: String,
+           stage: impl Fn(CpuPipeline, &RgbaImage) -> CpuPipeline + Send + Sync + 'static) {
+        let stage: Stage = Arc::new(stage);
+        let logged = self.logged.clone();
+        let logged_stage = stage.clone();
+        self.calculate(
+            move |surface| {
+                let (data, profile) = stage(CpuPipeline::default(), surface)
+                    .apply_profiled(surface);
+                (data, profile.total_duration())
+            },
+            move |duration| logged(LogEntry { operation, duration, stage: logged_stage })
+        );
+    }
+
+    /// Applies the logged operations, in order, as a single pipeline
+    pub fn replay(&self, entries: Vec<LogEntry>) {
+        println!("Replaying {} operations", entries.len());
+        self.calculate(move |surface| (entries.iter()
+                .fold(CpuPipeline::default(), |pipeline, entry| (entry.stage)(pipeline, surface))
+                .apply(surface), ()),
+            |_| ());
+    }
+
+    /// Called with every operation applied through this image
+    pub fn connect_logged(&self, f: impl Fn(LogEntry) + 'static) {
+        self.logged.connect(f);
+    }
+
+    /// Computes the histogram of the non-zero Canny edge strengths in the
+    /// background, and hands it to `f` on the main loop
+    pub fn gradient_histogram(&self, f: impl FnOnce(Histogram) + 'static) {
+        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+        let pixbuf = self.pixbuf.clone();
+
+        thread::spawn(move || {
+            let surface = pixbuf.read().unwrap();
+            let strength = CpuPipeline::default()
+                .canny_strength()
+                .apply(&*surface);
+            let values = (0..strength.width())
+                .flat_map(|x| (0..strength.height()).map(move |y| (x, y)))
+                .map(|(x, y)| strength[(x, y)].intensity())
+                .filter(|&v| v > 0.0);
+            sender.send(Histogram::from_values(HISTOGRAM_BINS, values))
+                .expect("Could not send through channel");
+        });
+
+        let mut f = Some(f);
+        receiver.attach(
+            None,
+            move |histogram| {
+                if let Some(f) = f.take() {
+                    f(histogram);
+                }
                 Continue(false)
             }
         );
@@ -124,7 +216,8 @@ impl Image {
     pub fn downgrade(&self) -> WeakImage {
         WeakImage {
             pixbuf: Arc::downgrade(&self.pixbuf),
-            stack: self.stack.downgrade()
+            stack: self.stack.downgrade(),
+            logged: self.logged.clone(),
         }
     }
 
@@ -132,51 +225,85 @@ impl Image {
         assert_ne!(size % 2, 0);
         println!("Gaussian Blur: {:#?}", size);
 
-        self.calculate(move |surface| CpuPipeline::default()
+        self.run(format!("Gaussian Blur size={size}"), move |pipeline, _| pipeline
             .filter(CpuGenerator::new(size)
-                .gaussian_needle((size >> 1 + 1) as f64 / 10.0 + 0.1))
-            .apply(&surface.clone().into())
-            .into());
+                .gaussian_needle((size >> 1 + 1) as f64 / 10.0 + 0.1)));
+    }
+
+    pub fn unsharp_mask(&self, radius: f64, amount: f64) {
+        println!("Unsharp mask: {:#?} {:#?}", radius, amount);
+
+        self.run(format!("Sharpen radius={radius} amount={amount}"), move |pipeline, _| pipeline
+            .unsharp_mask(radius, amount, 0.0));
+    }
+
+    /// Shows what a gaussian blur of `size` would remove, as a colorized
+    /// difference to the blurred image
+    pub fn blur_difference(&self, size: usize, amplify: f64) {
+        self.run(format!("Blur difference size={size} amplify={amplify}"), move |pipeline, _| pipeline
+            .diff(CpuPipeline::default()
+                .filter(CpuGenerator::new(size)
+                    .gaussian_needle((size >> 1 + 1) as f64 / 10.0 + 0.1)), amplify, true))
     }
 
     pub fn snp_noise(&self, variance: f64) {
         println!("S&P noise: {:#?}", variance);
-        self.calculate(move |surface| CpuPipeline::default()
+        self.run(format!("Salt & Pepper Noise variance={variance}"), move |pipeline, surface| pipeline
             .add(CpuGenerator::new(surface.width().max(surface.height()) as usize)
-                .salt_and_pepper_noise(variance))
-            .apply(&surface.clone().into())
-            .into())
+                .salt_and_pepper_noise(variance)))
     }
 
     pub fn gaussian_noise(&self, variance: f64, intensity: f64) {
         println!("Gaussian Noise: {:#?}", variance);
 
-        self.calculate(move |surface| CpuPipeline::default()
+        self.run(format!("Gaussian Noise variance={variance} intensity={intensity}"), move |pipeline, surface| pipeline
             .add(CpuGenerator::new(surface.width().max(surface.height()) as usize)
-                .gaussian_noise(0.5, variance, intensity))
-            .apply(&surface.clone().into())
-            .into())
+                .gaussian_noise(0.5, variance, intensity)))
     }
 
-    pub fn canny(&self, threshold: Vec<f64>) {
-        self.calculate(move |surface| CpuPipeline::default()
-            .canny(threshold)
-            .apply(&surface.clone().into())
-            .into())
+    pub fn canny(&self, low: f64, high: f64) {
+        self.run(format!("Canny low={low} high={high}"), move |pipeline, _| pipeline
+            .canny(low, high))
     }
     
+    pub fn auto_canny(&self) {
+        self.run("Auto Canny".to_string(), |pipeline, _| pipeline
+            .auto_canny())
+    }
+
+    pub fn brightness_contrast(&self, brightness: f64, contrast: f64) {
+        self.run(format!("Brightness & Contrast brightness={brightness} contrast={contrast}"), move |pipeline, _| pipeline
+            .brightness_contrast(brightness, contrast))
+    }
+
+    pub fn posterize(&self, levels: usize) {
+        self.run(format!("Posterize levels={levels}"), move |pipeline, _| pipeline
+            .posterize(levels))
+    }
+
+    pub fn solarize(&self, threshold: f64) {
+        self.run(format!("Solarize threshold={threshold}"), move |pipeline, _| pipeline
+            .solarize(threshold))
+    }
+
+    pub fn sepia(&self) {
+        self.run("Sepia".to_string(), |pipeline, _| pipeline
+            .sepia())
+    }
+
     pub fn grayscale(&self) {
-        self.calculate(move |surface| CpuPipeline::default()
-            .grayscale()
-            .apply(&surface.clone().into())
-            .into())
+        self.run("Grayscale".to_string(), |pipeline, _| pipeline
+            .grayscale())
     }
     
     pub fn gradient(&self) {
-        self.calculate(move |surface| CpuPipeline::default()
-            .gradient()
-            .apply(&surface.clone().into())
-            .into())
+        self.run("Gradient".to_string(), |pipeline, _| pipeline
+            .gradient())
+    }
+
+    pub fn normalize(&self) {
+        self.run("Normalize".to_string(), |pipeline, _| pipeline
+            .normalize())
     }
 }
 
@@ -184,7 +311,8 @@ impl WeakImage {
     pub fn upgrade(&self) -> Option<Image> {
         Some(Image {
             pixbuf: self.pixbuf.upgrade()?,
-            stack: self.stack.upgrade()?
+            stack: self.stack.upgrade()?,
+            logged: self.logged.clone(),
         })
     }
 }
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use gtk::prelude::*;
+use gtk::Widget;
+use crate::image::{LogEntry, WeakImage};
+use crate::util::{Addable, With};
+
+/// A collapsible list of the operations applied so far, with their timing,
+/// which can be copied as text or replayed on the current image
+#[derive(Clone)]
+pub struct LogPanel {
+    expander: gtk::Expander,
+    list: gtk::ListBox,
+    entries: Rc<RefCell<Vec<LogEntry>>>,
+}
+
+impl LogPanel {
+    pub fn new(image: WeakImage) -> LogPanel {
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .build();
+        let entries = Rc::new(RefCell::new(vec![]));
+
+        let expander = gtk::Expander::builder()
+            .label("Log")
+            .build()
+            .with(|w| {
+                gtk::Box::builder()
+                    .orientation(gtk::Orientation::Vertical)
+                    .spacing(6)
+                    .build()
+                    .put_in(&w)
+                    .with(|w| {
+                        list.clone()
+                            .put_in(&w);
+
+                        gtk::Box::builder()
+                            .orientation(gtk::Orientation::Horizontal)
+                            .spacing(6)
+                            .homogeneous(true)
+                            .build()
+                            .put_in(&w)
+                            .with(|w| {
+                                let copied = entries.clone();
+                                gtk::Button::builder()
+                                    .label("Copy")
+                                    .build()
+                                    .put_in(&w)
+                                    .connect_clicked(move |b| b.clipboard()
+                                        .set_text(&log_text(&copied.borrow())));
+
+                                let replayed = entries.clone();
+                                gtk::Button::builder()
+                                    .label("Replay")
+                                    .build()
+                                    .put_in(&w)
+                                    .connect_clicked(move |_| image
+                                        .upgrade()
+                                        .unwrap()
+                                        .replay(replayed.borrow().clone()));
+
+                                let (cleared, list) = (entries.clone(), list.clone());
+                                gtk::Button::builder()
+                                    .label("Clear")
+                                    .build()
+                                    .put_in(&w)
+                                    .connect_clicked(move |_| {
+                                        cleared.borrow_mut().clear();
+                                        while let Some(row) = list.first_child() {
+                                            list.remove(&row);
+                                        }
+                                    });
+                            });
+                    });
+                w
+            });
+
+        LogPanel { expander, list, entries }
+    }
+
+    pub fn push(&self, entry: LogEntry) {
+        self.list.append(&gtk::Label::builder()
+            .xalign(0f32)
+            .label(&log_line(&entry))
+            .build());
+        self.entries.borrow_mut().push(entry);
+    }
+
+    pub fn as_widget(&self) -> impl IsA<Widget> {
+        self.expander.clone()
+    }
+}
+
+fn log_line(entry: &LogEntry) -> String {
+    format!("{} ({:.1} ms)", entry.operation, entry.duration.as_secs_f64() * 1000.0)
+}
+
+fn log_text(entries: &[LogEntry]) -> String {
+    entries.iter()
+        .map(|entry| log_line(entry) + "\n")
+        .collect()
+}
@@ -4,6 +4,8 @@
 mod image;
 pub mod util;
 mod section;
+mod histogram;
+mod log;
 
 #[macro_use]
 extern crate computer_vision;
@@ -13,7 +15,9 @@ use std::collections::HashMap;
 use gtk::{Application, FileFilter, Widget};
 use gtk::prelude::*;
 use util::With;
+use crate::histogram::HistogramPanel;
 use crate::image::*;
+use crate::log::LogPanel;
 use crate::section::SectionBuilder;
 use crate::util::{Addable, AddableAt, Event, Side, Title};
 
@@ -134,6 +138,42 @@ fn build_ui(app: &Application) {
                                 .build()
                                 .put_in(&w);
 
+                            SectionBuilder::builder()
+                                .label("Sharpen")
+                                .scale("radius", 1..10)
+                                .scale("amount", 0..30)
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |d: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .unsharp_mask(d[0], d[1] / 10.0)))
+                                .build()
+                                .put_in(&w);
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
+                            SectionBuilder::builder()
+                                .label("Blur difference")
+                                .scale("size", 1..8)
+                                .scale("amplify", 1..32)
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |d: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .blur_difference(d[0] as usize * 2 + 1, d[1])))
+                                .build()
+                                .put_in(&w);
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
 
                             SectionBuilder::builder()
                                 .label("Gaussian Noise")
@@ -170,6 +210,74 @@ fn build_ui(app: &Application) {
                                 .build()
                                 .put_in(&w);
 
+                            SectionBuilder::builder()
+                                .label("Brightness & Contrast")
+                                .scale("brightness", 0..200)
+                                .scale("contrast", 0..200)
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |d: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .brightness_contrast((d[0] - 100.0) / 100.0, d[1] / 100.0)))
+                                .build()
+                                .put_in(&w);
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
+                            SectionBuilder::builder()
+                                .label("Posterize")
+                                .scale("levels", 2..16)
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |d: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .posterize(d[0] as usize)))
+                                .build()
+                                .put_in(&w);
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
+                            SectionBuilder::builder()
+                                .label("Solarize")
+                                .scale("threshold", 0..100)
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |d: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .solarize(d[0] / 100.0)))
+                                .build()
+                                .put_in(&w);
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
+                            SectionBuilder::builder()
+                                .label("Sepia")
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |_: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .sepia()))
+                                .build()
+                                .put_in(&w);
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
                             SectionBuilder::builder()
                                 .label("Grayscale")
                                 .sensitivity_event(&load)
@@ -203,17 +311,68 @@ fn build_ui(app: &Application) {
                                 .put_in(&w);
 
                             SectionBuilder::builder()
+                                .label("Normalize")
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |_: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .normalize()))
+                                .build()
+                                .put_in(&w);
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
+                            let canny = SectionBuilder::builder()
                                 .label("Canny")
                                 .expandable(true)
-                                .scale("threshold 1", 0..1)
-                                .scale("threshold 2", 0..1)
-                                .scale("threshold 3", 0..1)
+                                .scale("low threshold", 0..1)
+                                .scale("high threshold", 0..1)
                                 .sensitivity_event(&load)
                                 .connect_clicked(i.clone()
                                     .with(|i| move |d: &[f64]| i
                                         .upgrade()
                                         .unwrap()
-                                        .canny(d.to_vec())))
+                                        .canny(d[0], d[1])));
+                            let low = canny.scale_handle("low threshold").unwrap();
+                            let high = canny.scale_handle("high threshold").unwrap();
+                            canny.build()
+                                .put_in(&w);
+
+                            // Dragging the markers on the edge strength histogram
+                            // sets the Canny thresholds
+                            let panel = HistogramPanel::new();
+                            panel.connect_thresholds(move |l, h| {
+                                low.set_value(l);
+                                high.set_value(h);
+                            });
+                            panel.as_widget()
+                                .put_in(&w);
+                            load.connect(i.clone()
+                                .with(|i| move || {
+                                    let panel = panel.clone();
+                                    i
+                                        .upgrade()
+                                        .unwrap()
+                                        .gradient_histogram(move |h| panel.set_histogram(h))
+                                }));
+
+                            gtk::Separator::builder()
+                                .orientation(gtk::Orientation::Horizontal)
+                                .build()
+                                .put_in(&w);
+
+                            SectionBuilder::builder()
+                                .label("Auto Canny")
+                                .sensitivity_event(&load)
+                                .connect_clicked(i.clone()
+                                    .with(|i| move |_: &[f64]| i
+                                        .upgrade()
+                                        .unwrap()
+                                        .auto_canny()))
                                 .build()
                                 .put_in(&w);
 
@@ -221,6 +380,13 @@ fn build_ui(app: &Application) {
                                 .orientation(gtk::Orientation::Horizontal)
                                 .build()
                                 .put_in(&w);
+
+                            let log = LogPanel::new(i.clone());
+                            log.as_widget()
+                                .put_in(&w);
+                            i.upgrade()
+                                .unwrap()
+                                .connect_logged(move |entry| log.push(entry));
                         });
 
                     gtk::Separator::builder()
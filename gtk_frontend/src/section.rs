@@ -39,6 +39,13 @@ impl SectionBuilder {
         self
     }
 
+    /// The scale added under `name`, to drive it from elsewhere
+    pub fn scale_handle(&self, name: &str) -> Option<gtk::Scale> {
+        self.scales.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, s)| s.clone())
+    }
+
     pub fn label(mut self, name: &str) -> Self {
         self.name = Some(name.to_string());
         self.button.set_label(name);
@@ -0,0 +1,95 @@
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+
+/// What a backend can do, so callers can pick one that fits
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Runs on a graphics card
+    pub gpu: bool,
+    /// Splits work across threads
+    pub threaded: bool,
+    /// Every stage of the `Pipeline` trait is implemented
+    pub full_pipeline: bool,
+}
+
+/// A pipeline implementation, as known to the registry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backend {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub capabilities: Capabilities,
+}
+
+pub const CPU: Backend = Backend {
+    name: "cpu",
+    description: "Reference implementation on the CPU, see `cpu::CpuPipeline`",
+    capabilities: Capabilities {
+        gpu: false,
+        threaded: false,
+        full_pipeline: true,
+    },
+};
+
+struct Registry {
+    backends: Vec<Backend>,
+    default: &'static str,
+}
+
+lazy_static! {
+    static ref REGISTRY: RwLock<Registry> = RwLock::new(Registry {
+        backends: vec![CPU],
+        default: CPU.name,
+    });
+}
+
+/// The backend used when none is asked for, `cpu` unless changed
+impl Default for Backend {
+    fn default() -> Backend {
+        let registry = REGISTRY.read().unwrap();
+        registry.backends.iter()
+            .find(|b| b.name == registry.default)
+            .cloned()
+            .unwrap()
+    }
+}
+
+impl Backend {
+    /// Makes a backend available by name. Names are unique
+    pub fn register(backend: Backend) -> Result<(), String> {
+        let mut registry = REGISTRY.write().unwrap();
+        if registry.backends.iter().any(|b| b.name == backend.name) {
+            return Err(format!("Backend '{}' is already registered", backend.name));
+        }
+        registry.backends.push(backend);
+        Ok(())
+    }
+
+    pub fn by_name(name: &str) -> Option<Backend> {
+        REGISTRY.read().unwrap()
+            .backends.iter()
+            .find(|b| b.name == name)
+            .cloned()
+    }
+
+    pub fn set_default(name: &str) -> Result<(), String> {
+        let backend = Backend::by_name(name)
+            .ok_or_else(|| format!("Unknown backend '{name}'"))?;
+        REGISTRY.write().unwrap().default = backend.name;
+        Ok(())
+    }
+
+    /// Every registered backend, in order of registration
+    pub fn all() -> Vec<Backend> {
+        REGISTRY.read().unwrap().backends.clone()
+    }
+
+    /// The first registered backend with at least the given capabilities
+    pub fn with_capabilities(wanted: Capabilities) -> Option<Backend> {
+        REGISTRY.read().unwrap()
+            .backends.iter()
+            .find(|b| (!wanted.gpu || b.capabilities.gpu)
+                && (!wanted.threaded || b.capabilities.threaded)
+                && (!wanted.full_pipeline || b.capabilities.full_pipeline))
+            .cloned()
+    }
+}
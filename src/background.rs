@@ -0,0 +1,99 @@
+use crate::cpu::Image;
+use crate::mask::Mask;
+use crate::pipeline::Image as _;
+use crate::rgba::Rgba;
+
+/// A single running gaussian per pixel over the intensity of a video:
+/// pixels far from their usual value, in standard deviations, are
+/// foreground
+pub struct BackgroundModel {
+    mean: Vec<Vec<f64>>,
+    variance: Vec<Vec<f64>>,
+    /// How fast the model follows changes, in (0, 1]
+    learning_rate: f64,
+    /// Number of standard deviations beyond which a pixel is foreground
+    threshold: f64,
+}
+
+/// Keeps a pixel that never changes from flagging every bit of noise
+const MIN_VARIANCE: f64 = 1e-4;
+
+impl BackgroundModel {
+    /// Starts from `first` as the background
+    pub fn new(first: &Image, learning_rate: f64, threshold: f64) -> BackgroundModel {
+        assert!(learning_rate > 0.0 && learning_rate <= 1.0, "Learning rate must be in (0, 1], got {learning_rate}");
+        BackgroundModel {
+            mean: (0..first.width())
+                .map(|x| (0..first.height())
+                    .map(|y| first[(x, y)].intensity())
+                    .collect())
+                .collect(),
+            variance: vec![vec![MIN_VARIANCE; first.height()]; first.width()],
+            learning_rate,
+            threshold,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.mean.first().map_or(0, Vec::len)
+    }
+
+    fn check_size(&self, frame: &Image) {
+        assert_eq!((frame.width(), frame.height()), (self.width(), self.height()),
+                   "Frame size differs from the background model");
+    }
+
+    /// Blends `frame` into the model. Foreground pixels are learnt too, so
+    /// objects that stop moving fade into the background
+    pub fn update(&mut self, frame: &Image) {
+        self.check_size(frame);
+        let rate = self.learning_rate;
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                let difference = frame[(x, y)].intensity() - self.mean[x][y];
+                self.mean[x][y] += rate * difference;
+                self.variance[x][y] = ((1.0 - rate) * (self.variance[x][y] + rate * difference * difference))
+                    .max(MIN_VARIANCE);
+            }
+        }
+    }
+
+    /// The pixels of `frame` that do not fit the background
+    pub fn foreground_mask(&self, frame: &Image) -> Mask {
+        self.check_size(frame);
+        Mask::from_fn(self.width(), self.height(), |x, y| {
+            let difference = frame[(x, y)].intensity() - self.mean[x][y];
+            difference * difference > self.threshold * self.threshold * self.variance[x][y]
+        })
+    }
+
+    /// The mean background as an image
+    pub fn background(&self) -> Image {
+        Image::from_fn(self.width(), self.height(), |x, y| Rgba::gray(self.mean[x][y]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::BackgroundModel;
+
+    #[test]
+    fn moving_square_is_foreground() {
+        let frame = |offset: usize| Image::from_fn(20, 10, |x, y| Rgba::gray(
+            if (offset..offset + 3).contains(&x) && (3..6).contains(&y) { 0.9 } else { 0.3 + (x % 3) as f64 * 0.01 }));
+        let mut model = BackgroundModel::new(&frame(100), 0.1, 3.0);
+        for _ in 0..5 {
+            model.update(&frame(100));
+        }
+        let mask = model.foreground_mask(&frame(8));
+        assert_eq!(mask.count(), 9);
+        assert!(mask.get(9, 4));
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use crate::cpu::{CpuPipeline, Image};
+use crate::pipeline::Pipeline;
+
+/// A pipeline stage that can be rebuilt on demand, identified by a hash of
+/// its name and parameters
+pub struct Stage {
+    key: u64,
+    build: Box<dyn Fn() -> CpuPipeline>,
+}
+
+impl Stage {
+    /// `params` must capture everything that changes the stage's output.
+    /// Floating point parameters can be hashed through `f64::to_bits`
+    pub fn new(name: &str, params: impl Hash, build: impl Fn() -> CpuPipeline + 'static) -> Stage {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        params.hash(&mut hasher);
+        Stage {
+            key: hasher.finish(),
+            build: Box::new(build),
+        }
+    }
+}
+
+/// Remembers the output of every stage of the last run, so that a new run on
+/// the same input only recomputes the stages after the first changed one
+#[derive(Default)]
+pub struct CachedPipeline {
+    input: Option<u64>,
+    outputs: Vec<(u64, Image)>,
+}
+
+impl CachedPipeline {
+    pub fn new() -> CachedPipeline {
+        CachedPipeline::default()
+    }
+
+    pub fn apply(&mut self, image: &Image, stages: &[Stage]) -> Image {
+        let input = image.fingerprint();
+        if self.input != Some(input) {
+            self.input = Some(input);
+            self.outputs.clear();
+        }
+
+        let reused = self.outputs.iter()
+            .zip(stages)
+            .take_while(|((key, _), stage)| *key == stage.key)
+            .count();
+        self.outputs.truncate(reused);
+
+        for stage in &stages[reused..] {
+            let out = (stage.build)()
+                .apply(self.outputs.last()
+                    .map(|(_, image)| image)
+                    .unwrap_or(image));
+            self.outputs.push((stage.key, out));
+        }
+
+        self.outputs.last()
+            .map(|(_, image)| image.clone())
+            .unwrap_or_else(|| image.clone())
+    }
+
+    /// How many stages of the last run have a cached output
+    pub fn cached_stages(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.input = None;
+        self.outputs.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use crate::cpu::{CpuPipeline, Image};
+    use crate::pipeline::Pipeline;
+    use super::{CachedPipeline, Stage};
+
+    #[test]
+    fn only_stages_after_a_change_are_recomputed() {
+        let image = Image::empty(4, 4);
+        let builds = Rc::new(Cell::new(0));
+        let stages = |size: usize| {
+            let (first, second) = (builds.clone(), builds.clone());
+            vec![
+                Stage::new("grayscale", (), move || {
+                    first.set(first.get() + 1);
+                    CpuPipeline::default().grayscale()
+                }),
+                Stage::new("blur", size, move || {
+                    second.set(second.get() + 1);
+                    CpuPipeline::default().box_filter_integral(size)
+                }),
+            ]
+        };
+        let mut cache = CachedPipeline::new();
+        cache.apply(&image, &stages(3));
+        assert_eq!(builds.get(), 2);
+        cache.apply(&image, &stages(3));
+        assert_eq!(builds.get(), 2);
+        cache.apply(&image, &stages(5));
+        assert_eq!(builds.get(), 3);
+    }
+}
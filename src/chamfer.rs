@@ -0,0 +1,130 @@
+use crate::cpu::Image;
+
+/// Intensity above which a pixel of an edge map counts as an edge
+const EDGE_THRESHOLD: f64 = 0.5;
+
+/// Euclidean distance from every pixel to the nearest pixel whose intensity
+/// is above `threshold`, indexed as `[x][y]`. Infinite if there is none
+pub fn distance_transform(image: &Image, threshold: f64) -> Vec<Vec<f64>> {
+    let (width, height) = (image.width(), image.height());
+    let mut squared = (0..width)
+        .map(|x| (0..height)
+            .map(|y| if image[(x, y)].intensity() > threshold { 0.0 } else { f64::INFINITY })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    for column in squared.iter_mut() {
+        *column = distance_1d(column);
+    }
+    for y in 0..height {
+        let row = squared.iter()
+            .map(|column| column[y])
+            .collect::<Vec<_>>();
+        for (column, d) in squared.iter_mut().zip(distance_1d(&row)) {
+            column[y] = d;
+        }
+    }
+    squared.into_iter()
+        .map(|column| column.into_iter().map(f64::sqrt).collect())
+        .collect()
+}
+
+/// Squared distance transform of a sampled function, by the lower envelope
+/// of parabolas (Felzenszwalb & Huttenlocher)
+fn distance_1d(f: &[f64]) -> Vec<f64> {
+    let sources = (0..f.len())
+        .filter(|q| f[*q].is_finite())
+        .collect::<Vec<_>>();
+    if sources.is_empty() {
+        return f.to_vec();
+    }
+    let intersection = |p: usize, q: usize|
+        ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2.0 * (q as f64 - p as f64));
+
+    let mut hull: Vec<usize> = Vec::new();
+    let mut starts: Vec<f64> = Vec::new();
+    for q in sources {
+        let mut s = f64::NEG_INFINITY;
+        while let Some(&p) = hull.last() {
+            s = intersection(p, q);
+            if s <= *starts.last().unwrap() {
+                hull.pop();
+                starts.pop();
+                s = f64::NEG_INFINITY;
+            } else {
+                break;
+            }
+        }
+        hull.push(q);
+        starts.push(s);
+    }
+
+    let mut k = 0;
+    (0..f.len())
+        .map(|x| {
+            while k + 1 < hull.len() && starts[k + 1] < x as f64 {
+                k += 1;
+            }
+            let p = hull[k];
+            (x as f64 - p as f64).powi(2) + f[p]
+        })
+        .collect()
+}
+
+/// Scores every placement of `template_edges` inside `image_edges` by the
+/// mean distance from the template's edge pixels to the nearest image edge.
+/// Lower is better. Entry `[x][y]` is the placement with the template's top
+/// left corner at `(x, y)`
+pub fn chamfer_match(template_edges: &Image, image_edges: &Image) -> Vec<Vec<f64>> {
+    let distances = distance_transform(image_edges, EDGE_THRESHOLD);
+    let points = (0..template_edges.width())
+        .flat_map(|x| (0..template_edges.height())
+            .map(move |y| (x, y)))
+        .filter(|&(x, y)| template_edges[(x, y)].intensity() > EDGE_THRESHOLD)
+        .collect::<Vec<_>>();
+    let columns = (image_edges.width() + 1).saturating_sub(template_edges.width());
+    let rows = (image_edges.height() + 1).saturating_sub(template_edges.height());
+    (0..columns)
+        .map(|x| (0..rows)
+            .map(|y| points.iter()
+                .map(|(i, j)| distances[x + i][y + j])
+                .sum::<f64>() / points.len().max(1) as f64)
+            .collect())
+        .collect()
+}
+
+/// The placement with the lowest score, as `(x, y, score)`
+pub fn best_match(scores: &[Vec<f64>]) -> Option<(usize, usize, f64)> {
+    scores.iter()
+        .enumerate()
+        .flat_map(|(x, column)| column.iter()
+            .enumerate()
+            .map(move |(y, score)| (x, y, *score)))
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::{best_match, chamfer_match, distance_transform};
+
+    #[test]
+    fn distances_and_matching_find_the_edges() {
+        let dot = Image::from_fn(5, 4, |x, y| Rgba::gray(if (x, y) == (1, 1) { 1.0 } else { 0.0 }));
+        let distances = distance_transform(&dot, 0.5);
+        assert_eq!(distances[1][1], 0.0);
+        assert_eq!(distances[4][1], 3.0);
+        assert!((distances[4][3] - 13f64.sqrt()).abs() < 1e-12);
+        assert!(distance_transform(&Image::empty(2, 2), 0.5)[0][0].is_infinite());
+
+        // An L shape hidden at (3, 2) in a larger edge map
+        let corner = |x: usize, y: usize| (x == 0 && y < 3) || (y == 2 && x < 3);
+        let template = Image::from_fn(3, 3, |x, y| Rgba::gray(if corner(x, y) { 1.0 } else { 0.0 }));
+        let edges = Image::from_fn(8, 7, |x, y| Rgba::gray(
+            if x >= 3 && y >= 2 && corner(x - 3, y - 2) { 1.0 } else { 0.0 }));
+        let scores = chamfer_match(&template, &edges);
+        assert_eq!((scores.len(), scores[0].len()), (6, 5));
+        assert_eq!(best_match(&scores), Some((3, 2, 0.0)));
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+use crate::cpu::{make_contact_sheet, CpuGenerator, CpuPipeline, Image};
+use crate::pipeline::{Generator, Pipeline};
+use crate::{Axis, GradientNorm};
+
+/// How one gradient operator responded to an image
+#[derive(Clone, Debug)]
+pub struct OperatorStats {
+    pub name: &'static str,
+    /// Mean and largest normalized magnitude
+    pub mean: f64,
+    pub max: f64,
+    /// Fraction of the pixels whose magnitude is above the edge threshold
+    pub edge_fraction: f64,
+    pub duration: Duration,
+}
+
+/// The operators that are compared, as normalized L2 gradient magnitudes.
+/// The derivative of gaussian uses a 7x7 kernel with a sigma of 1
+pub fn gradient_operators() -> Vec<(&'static str, CpuPipeline)> {
+    let small = CpuGenerator::new(3);
+    let dog = CpuGenerator::new(7);
+    let gradient = |x, y| CpuPipeline::default().gradient_with(x, y, GradientNorm::L2, true);
+    vec![
+        ("sobel", gradient(small.sobel_x(), small.sobel_y())),
+        ("scharr", gradient(small.scharr_x(), small.scharr_y())),
+        ("prewitt", gradient(small.prewitt_x(), small.prewitt_y())),
+        ("dog", gradient(dog.gaussian_derivative_needle(1.0, 1, Axis::X),
+                         dog.gaussian_derivative_needle(1.0, 1, Axis::Y))),
+    ]
+}
+
+/// Runs every operator of `gradient_operators` over `image`. Returns their
+/// outputs side by side, in the same order, along with their statistics
+pub fn compare_gradients(image: &Image, edge_threshold: f64) -> (Image, Vec<OperatorStats>) {
+    let (outputs, stats): (Vec<_>, Vec<_>) = gradient_operators().into_iter()
+        .map(|(name, pipeline)| {
+            let start = Instant::now();
+            let output = pipeline.apply(image);
+            let duration = start.elapsed();
+            let magnitudes = (0..output.width())
+                .flat_map(|x| (0..output.height())
+                    .map(move |y| (x, y)))
+                .map(|p| output[p].intensity())
+                .collect::<Vec<_>>();
+            let count = magnitudes.len().max(1) as f64;
+            let stats = OperatorStats {
+                name,
+                mean: magnitudes.iter().sum::<f64>() / count,
+                max: magnitudes.iter().copied().fold(0.0, f64::max),
+                edge_fraction: magnitudes.iter().filter(|m| **m > edge_threshold).count() as f64 / count,
+                duration,
+            };
+            (output, stats)
+        })
+        .unzip();
+    let composite = make_contact_sheet(&outputs, outputs.len(), image.width().max(image.height()), None);
+    (composite, stats)
+}
+
+/// Every combination of one value from `first` and one from `second`,
+/// for sweeping over two parameters such as Canny's thresholds
+pub fn grid(first: &[f64], second: &[f64]) -> Vec<(f64, f64)> {
+    first.iter()
+        .flat_map(|a| second.iter()
+            .map(move |b| (*a, *b)))
+        .collect()
+}
+
+/// Applies the pipeline built by `template` for every parameter value, in order
+pub fn sweep<P>(image: &Image, parameters: &[P], template: impl Fn(CpuPipeline, &P) -> CpuPipeline) -> Vec<Image> {
+    parameters.iter()
+        .map(|parameter| template(CpuPipeline::default(), parameter).apply(image))
+        .collect()
+}
+
+/// Like `sweep`, with the results tiled `columns` to a row
+pub fn sweep_sheet<P>(image: &Image, parameters: &[P], columns: usize, template: impl Fn(CpuPipeline, &P) -> CpuPipeline) -> Image {
+    let results = sweep(image, parameters, template);
+    make_contact_sheet(&results, columns, image.width().max(image.height()), None)
+}
@@ -0,0 +1,179 @@
+use crate::mask::Mask;
+
+/// Which points of a border are kept
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ContourApproximation {
+    /// Every border pixel
+    None,
+    /// Only the ends of horizontal, vertical and diagonal runs
+    Simple,
+}
+
+/// A border between set and unset pixels of a mask
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    /// Border pixels as `(x, y)`, following the border
+    pub points: Vec<(usize, usize)>,
+    /// Whether this is the border of a hole, rather than the outer border of a region
+    pub hole: bool,
+    /// Index of the enclosing contour, `None` for outer borders at the top level
+    pub parent: Option<usize>,
+}
+
+/// 8-neighbourhood as `(dy, dx)`, clockwise from east with `y` pointing down
+const AROUND: [(i64, i64); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+
+fn direction(from: (i64, i64), to: (i64, i64)) -> usize {
+    AROUND.iter()
+        .position(|&d| d == (to.0 - from.0, to.1 - from.1))
+        .unwrap()
+}
+
+/// Outer borders and hole borders of the set regions of a mask, with their
+/// nesting, by Suzuki and Abe's border following
+pub fn find_contours(mask: &Mask, approximation: ContourApproximation) -> Vec<Contour> {
+    let (width, height) = (mask.width() as i64, mask.height() as i64);
+    // Rows then columns, with a border of unset pixels around
+    let mut f = (0..height + 2)
+        .map(|i| (0..width + 2)
+            .map(|j| {
+                let inside = i > 0 && j > 0 && i <= height && j <= width;
+                (inside && mask.get((j - 1) as usize, (i - 1) as usize)) as i32
+            })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let at = |f: &Vec<Vec<i32>>, (i, j): (i64, i64)| f[i as usize][j as usize];
+
+    let mut contours: Vec<Contour> = vec![];
+    // Border numbers start at 2, 1 being the frame around the image
+    let mut nbd = 1;
+    for i in 1..=height {
+        let mut lnbd = 1;
+        for j in 1..=width {
+            let value = at(&f, (i, j));
+            let start = if value == 1 && at(&f, (i, j - 1)) == 0 {
+                Some((false, (i, j - 1)))
+            } else if value >= 1 && at(&f, (i, j + 1)) == 0 {
+                if value > 1 {
+                    lnbd = value;
+                }
+                Some((true, (i, j + 1)))
+            } else {
+                None
+            };
+
+            if let Some((hole, from)) = start {
+                nbd += 1;
+                let previous = (lnbd > 1).then(|| &contours[(lnbd - 2) as usize]);
+                let parent = match previous {
+                    None => None,
+                    Some(previous) if previous.hole == hole => previous.parent,
+                    Some(_) => Some((lnbd - 2) as usize),
+                };
+                let points = follow_border(&mut f, (i, j), from, nbd);
+                contours.push(Contour {
+                    points: points.into_iter()
+                        .map(|(i, j)| ((j - 1) as usize, (i - 1) as usize))
+                        .collect(),
+                    hole,
+                    parent,
+                });
+            }
+
+            let value = at(&f, (i, j));
+            if value != 0 && value != 1 {
+                lnbd = value.abs();
+            }
+        }
+    }
+
+    if approximation == ContourApproximation::Simple {
+        for contour in &mut contours {
+            contour.points = simplify(&contour.points);
+        }
+    }
+    contours
+}
+
+/// Follows the border starting at `start`, coming from the unset pixel `from`,
+/// and marks it with `nbd`. Returns the border pixels in `(row, column)`
+fn follow_border(f: &mut [Vec<i32>], start: (i64, i64), from: (i64, i64), nbd: i32) -> Vec<(i64, i64)> {
+    let set = |f: &[Vec<i32>], (i, j): (i64, i64)| f[i as usize][j as usize] != 0;
+    let step = |p: (i64, i64), d: usize| (p.0 + AROUND[d].0, p.1 + AROUND[d].1);
+
+    // Clockwise from `from`, the first set neighbour
+    let d = direction(start, from);
+    let Some(first) = (0..8)
+        .map(|k| step(start, (d + k) % 8))
+        .find(|p| set(f, *p)) else {
+        f[start.0 as usize][start.1 as usize] = -nbd;
+        return vec![start];
+    };
+
+    let mut points = vec![];
+    let (mut previous, mut current) = (first, start);
+    loop {
+        points.push(current);
+        // Counterclockwise from the previous pixel, the next set neighbour
+        let d = direction(current, previous);
+        let mut east_unset = false;
+        let mut next = current;
+        for k in 1..=8 {
+            let n = (d + 8 - k) % 8;
+            let candidate = step(current, n);
+            if set(f, candidate) {
+                next = candidate;
+                break;
+            }
+            east_unset |= n == 0;
+        }
+
+        let cell = &mut f[current.0 as usize][current.1 as usize];
+        if east_unset {
+            *cell = -nbd;
+        } else if *cell == 1 {
+            *cell = nbd;
+        }
+
+        if next == start && current == first {
+            break;
+        }
+        previous = current;
+        current = next;
+    }
+    points
+}
+
+/// Drops the points in the middle of straight runs
+fn simplify(points: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let delta = |a: (usize, usize), b: (usize, usize)| (b.0 as i64 - a.0 as i64, b.1 as i64 - a.1 as i64);
+    let n = points.len();
+    (0..n)
+        .filter(|&k| {
+            let (before, here, after) = (points[(k + n - 1) % n], points[k], points[(k + 1) % n]);
+            delta(before, here) != delta(here, after)
+        })
+        .map(|k| points[k])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mask::Mask;
+    use super::{find_contours, ContourApproximation};
+
+    #[test]
+    fn ring_has_outer_border_and_hole() {
+        let ring = Mask::from_fn(7, 7, |x, y| (1..6).contains(&x) && (1..6).contains(&y) && (x, y) != (3, 3));
+        let contours = find_contours(&ring, ContourApproximation::Simple);
+        assert_eq!(contours.len(), 2);
+        assert!(!contours[0].hole);
+        assert_eq!(contours[0].parent, None);
+        assert_eq!(contours[0].points, vec![(1, 1), (1, 5), (5, 5), (5, 1)]);
+        assert!(contours[1].hole);
+        assert_eq!(contours[1].parent, Some(0));
+    }
+}
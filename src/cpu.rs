@@ -7,14 +7,27 @@ use std::iter::from_fn;
 use std::num::Wrapping;
 use std::ops::Range;
 use std::path::Path;
+use std::rc::Rc;
 use std::slice::SliceIndex;
 use std::vec::IntoIter;
-use image::{ImageResult, RgbaImage};
+use std::time::{Duration, Instant};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::fs::File;
+use std::io::BufReader;
+use image::{DynamicImage, ImageFormat, ImageResult, RgbaImage};
+use image::codecs::jpeg::JpegDecoder;
 use probability::distribution::{Continuous, Gaussian};
 use rand::{Rng, thread_rng};
-use crate::Filter;
-use crate::pipeline::{Generator, Pipeline};
+use crate::{Anchor, Axis, BorderMode, OutputSize, ChannelCorrection, ColorVisionDeficiency, DitherMethod, Filter, FrequencyResponse, GradientNorm, Interpolation, NoiseChannels, Orientation, PixelFormat, ReduceOp, Shrinkage, Stacking, StructuringElement};
+use crate::fft::{fft2d, Complex, Spectrum};
+use crate::wavelet::{haar2d, inverse_haar2d, shrink};
+use crate::histogram::Histogram;
+use crate::palette::{median_cut, nearest};
+use crate::integral::IntegralImage;
+use crate::pipeline::{Content, Generator, ImageInfo, Pipeline, Severity, ValidationIssue};
 use crate::rgba::Rgba;
+use crate::font::{draw_text, text_width, GLYPH_HEIGHT, GLYPH_WIDTH};
 
 #[derive(Clone)]
 pub struct Image(Vec<Vec<Rgba>>);
@@ -65,17 +78,660 @@ impl Image {
         Image::construct(self.width(), self.height(), f)
     }
 
+    /// Loads an image no larger than `max_dim` on either side. JPEGs are
+    /// downscaled by the decoder itself, which skips most of the decoding work
+    pub fn open_scaled(path: impl AsRef<Path>, max_dim: usize) -> ImageResult<Image> {
+        let reader = image::io::Reader::open(&path)?.with_guessed_format()?;
+        let decoded = match reader.format() {
+            Some(ImageFormat::Jpeg) => {
+                let mut decoder = JpegDecoder::new(BufReader::new(File::open(&path)?))?;
+                let side = max_dim.min(u16::MAX as usize) as u16;
+                decoder.scale(side, side)?;
+                DynamicImage::from_decoder(decoder)?
+            }
+            _ => reader.decode()?,
+        };
+        Ok(Image::from(decoded.into_rgba8()).thumbnail(max_dim))
+    }
+
     pub fn save(&self, path: impl AsRef<Path>) -> ImageResult<()> {
         Into::<RgbaImage>::into(self.clone())
             .save(path)
     }
 
+    /// Minimum, maximum, mean and standard deviation of every channel.
+    /// All zero for an empty image
+    pub fn stats(&self) -> ImageStats {
+        let pixels = self.0.iter()
+            .flatten()
+            .map(|&p| -> [f64; 4] { p.into() })
+            .collect::<Vec<_>>();
+        let count = pixels.len().max(1) as f64;
+        let channel = |c: usize| {
+            let values = pixels.iter().map(move |p| p[c]);
+            let mean = values.clone().sum::<f64>() / count;
+            let variance = values.clone().map(|v| (v - mean) * (v - mean)).sum::<f64>() / count;
+            let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+            if pixels.is_empty() { [0.0; 4] } else { [min, max, mean, variance.sqrt()] }
+        };
+        let channels = (0..4).map(channel).collect::<Vec<_>>();
+        let statistic = |i: usize| channels.iter().map(|c| c[i]).collect::<Rgba>();
+        ImageStats {
+            min: statistic(0),
+            max: statistic(1),
+            mean: statistic(2),
+            std_dev: statistic(3),
+        }
+    }
+
+    /// The pixels of the `(2 * radius + 1)` square window around `(x, y)`,
+    /// replicating the edges
+    pub fn neighbourhood(&self, x: usize, y: usize, radius: usize) -> Vec<Rgba> {
+        let radius = radius as i64;
+        (-radius..=radius)
+            .flat_map(|i| (-radius..=radius)
+                .map(move |j| (i, j)))
+            .map(|(i, j)| self[(
+                (x as i64 + i).clamp(0, self.width() as i64 - 1) as usize,
+                (y as i64 + j).clamp(0, self.height() as i64 - 1) as usize,
+            )])
+            .collect()
+    }
+
+    /// The pixel at possibly out of bounds coordinates, resolved by `border`
+    pub fn get(&self, x: i64, y: i64, border: BorderMode) -> Rgba {
+        let inside = |v: i64, size: usize| v >= 0 && v < size as i64;
+        let reflect = |v: i64, size: usize| {
+            let period = 2 * (size as i64 - 1);
+            if period == 0 {
+                return 0;
+            }
+            let v = v.rem_euclid(period);
+            (if v < size as i64 { v } else { period - v }) as usize
+        };
+        match border {
+            BorderMode::Constant(pixel) if !inside(x, self.width()) || !inside(y, self.height()) => pixel,
+            BorderMode::Reflect => self[(reflect(x, self.width()), reflect(y, self.height()))],
+            BorderMode::Wrap => self[(
+                x.rem_euclid(self.width() as i64) as usize,
+                y.rem_euclid(self.height() as i64) as usize,
+            )],
+            _ => self[(
+                x.clamp(0, self.width() as i64 - 1) as usize,
+                y.clamp(0, self.height() as i64 - 1) as usize,
+            )],
+        }
+    }
+
+    /// Correlates with `kernel`, placing the kernel's `anchor` on each pixel
+    pub fn convolve(&self, kernel: &Image, anchor: Anchor, border: BorderMode, reduce: ReduceOp) -> Image {
+        let (cx, cy) = anchor.resolve(kernel.width(), kernel.height());
+        self.similar(|x, y| {
+            let mut taps = (0..kernel.width())
+                .flat_map(|i| (0..kernel.height())
+                    .map(move |j| (i, j)))
+                .map(|(i, j)| self.get(
+                    x as i64 + i as i64 - cx,
+                    y as i64 + j as i64 - cy,
+                    border
+                ) * kernel[(i, j)]);
+            let first = taps.next().unwrap_or(Rgba::BLACK);
+            match reduce {
+                ReduceOp::Sum => taps.fold(first, std::ops::Add::add),
+                ReduceOp::Min => taps.fold(first, Rgba::min),
+                ReduceOp::Max => taps.fold(first, Rgba::max),
+            }
+        })
+    }
+
+    /// A kernel from row-major weights, `weights[y][x]`. Alpha carries the
+    /// weight too, so it is filtered like the colour channels
+    pub fn from_weights(weights: &[Vec<f64>]) -> Image {
+        let height = weights.len();
+        let width = weights.first().map(Vec::len).unwrap_or(0);
+        Image::construct(width, height, |x, y| Rgba::gray(weights[y][x]).with_alpha(weights[y][x]))
+    }
+
+    /// Folds the pixels at `offsets` around every pixel with `op`, replicating the edges
+    fn morphology(&self, offsets: &[(i64, i64)], op: impl Fn(Rgba, Rgba) -> Rgba) -> Image {
+        self.similar(|x, y| offsets.iter()
+            .map(|(i, j)| self.get(x as i64 + i, y as i64 + j, BorderMode::Replicate))
+            .reduce(&op)
+            .unwrap_or(self[(x, y)]))
+    }
+
+    /// The intensity at `(x, y)` when used as a weight map, replicating
+    /// the edges if the map is smaller than the image it weighs
+    fn weight(&self, x: usize, y: usize) -> f64 {
+        self.get(x as i64, y as i64, BorderMode::Replicate).intensity()
+    }
+
+    /// Correlates with `kernel` centred on each pixel, weighting every tap by
+    /// `weights` and normalizing by the total weight. Pixels without any
+    /// weight around them are left as they are
+    fn weighted_convolve(&self, kernel: &Image, weights: &Image, border: BorderMode) -> Image {
+        let (cx, cy) = Anchor::Center.resolve(kernel.width(), kernel.height());
+        self.similar(|x, y| {
+            let (sum, total) = (0..kernel.width())
+                .flat_map(|i| (0..kernel.height())
+                    .map(move |j| (i, j)))
+                .map(|(i, j)| {
+                    let (sx, sy) = (x as i64 + i as i64 - cx, y as i64 + j as i64 - cy);
+                    let weight = weights.get(sx, sy, BorderMode::Replicate).intensity();
+                    let tap = kernel[(i, j)] * weight;
+                    (self.get(sx, sy, border) * tap, tap)
+                })
+                .fold((Rgba::gray(0.0).with_alpha(0.0), Rgba::gray(0.0).with_alpha(0.0)),
+                      |(sum, total), (value, tap)| (sum + value, total + tap));
+            sum.into_iter()
+                .zip(total)
+                .zip(self[(x, y)])
+                .map(|((sum, total), original)| if total.abs() > f64::EPSILON {
+                    sum / total
+                } else {
+                    original
+                })
+                .collect()
+        })
+    }
+
+    /// Samples the image at fractional coordinates, clamping to the edges
+    pub fn bilinear(&self, x: f64, y: f64) -> Rgba {
+        let x = x.clamp(0.0, (self.width() - 1) as f64);
+        let y = y.clamp(0.0, (self.height() - 1) as f64);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let x1 = (x0 + 1).min(self.width() - 1);
+        let y1 = (y0 + 1).min(self.height() - 1);
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let top = self[(x0, y0)] * (1.0 - fx) + self[(x1, y0)] * fx;
+        let bottom = self[(x0, y1)] * (1.0 - fx) + self[(x1, y1)] * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Samples the image at fractional coordinates with a Catmull-Rom spline,
+    /// clamping to the edges
+    pub fn bicubic(&self, x: f64, y: f64) -> Rgba {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (wx, wy) = (catmull_rom(x - x0), catmull_rom(y - y0));
+        (0..4)
+            .flat_map(|i| (0..4)
+                .map(move |j| (i, j)))
+            .map(|(i, j)| self.get(x0 as i64 + i as i64 - 1, y0 as i64 + j as i64 - 1, BorderMode::Replicate)
+                * (wx[i] * wy[j]))
+            .fold(Rgba::gray(0.0).with_alpha(0.0), std::ops::Add::add)
+    }
+
+    /// Samples the image at fractional coordinates, clamping to the edges
+    pub fn sample(&self, x: f64, y: f64, interpolation: Interpolation) -> Rgba {
+        match interpolation {
+            Interpolation::Nearest => self[(
+                (x.round().max(0.0) as usize).min(self.width() - 1),
+                (y.round().max(0.0) as usize).min(self.height() - 1),
+            )],
+            Interpolation::Bilinear => self.bilinear(x, y),
+            Interpolation::Bicubic => self.bicubic(x, y),
+        }
+    }
+
+    /// The upright image, given how the stored pixels are oriented
+    pub fn oriented(&self, orientation: Orientation) -> Image {
+        let (width, height) = (self.width(), self.height());
+        let (mx, my) = (width.saturating_sub(1), height.saturating_sub(1));
+        match orientation {
+            Orientation::Normal => self.clone(),
+            Orientation::FlipHorizontal => self.similar(|x, y| self[(mx - x, y)]),
+            Orientation::Rotate180 => self.similar(|x, y| self[(mx - x, my - y)]),
+            Orientation::FlipVertical => self.similar(|x, y| self[(x, my - y)]),
+            Orientation::Transpose => Image::construct(height, width, |x, y| self[(y, x)]),
+            Orientation::Rotate90 => Image::construct(height, width, |x, y| self[(y, my - x)]),
+            Orientation::Transverse => Image::construct(height, width, |x, y| self[(mx - y, my - x)]),
+            Orientation::Rotate270 => Image::construct(height, width, |x, y| self[(mx - y, x)]),
+        }
+    }
+
+    /// Resamples through per-pixel coordinate maps: the output pixel `(x, y)`
+    /// is sampled from `(map_x[x][y], map_y[x][y])`. The output has the
+    /// size of the maps, which are indexed like images and must have columns
+    /// of the same length
+    pub fn remap(&self, map_x: &[Vec<f64>], map_y: &[Vec<f64>], interpolation: Interpolation) -> Image {
+        assert_eq!(map_x.len(), map_y.len(), "Coordinate maps differ in width");
+        let height = map_x.first().map(Vec::len).unwrap_or(0);
+        for (x, (column_x, column_y)) in map_x.iter().zip(map_y).enumerate() {
+            assert!(column_x.len() == height && column_y.len() == height,
+                    "Column {x} of the coordinate maps has {} and {} entries, expected {height}",
+                    column_x.len(), column_y.len());
+        }
+        Image::construct(map_x.len(), height, |x, y| self.sample(map_x[x][y], map_y[x][y], interpolation))
+    }
+
+    /// Convolves rows and then columns with the same 1D kernel, replicating the edges
+    fn separable(&self, kernel: &[f64]) -> Image {
+        let radius = (kernel.len() / 2) as i64;
+        let clamp = |v: i64, max: usize| v.clamp(0, max as i64 - 1) as usize;
+        let rows = self.similar(|x, y| kernel.iter()
+            .enumerate()
+            .map(|(n, k)| self[(clamp(x as i64 + n as i64 - radius, self.width()), y)] * *k)
+            .fold(Rgba::gray(0.0).with_alpha(0.0), std::ops::Add::add));
+        rows.similar(|x, y| kernel.iter()
+            .enumerate()
+            .map(|(n, k)| rows[(x, clamp(y as i64 + n as i64 - radius, rows.height()))] * *k)
+            .fold(Rgba::gray(0.0).with_alpha(0.0), std::ops::Add::add))
+    }
+
+    /// Blurs with a 5-tap binomial kernel and halves both dimensions
+    pub fn pyr_down(&self) -> Image {
+        let blurred = self.separable(&BINOMIAL);
+        Image::construct(self.width().div_ceil(2),
+                         self.height().div_ceil(2),
+                         |x, y| blurred[(2 * x, 2 * y)])
+    }
+
+    /// Keeps every `factor`th pixel in both directions, after a gaussian
+    /// blur that removes the detail too fine for the smaller image
+    pub fn decimate(&self, factor: usize) -> Image {
+        assert!(factor > 0, "Decimation factor must be positive");
+        if factor == 1 {
+            return self.clone();
+        }
+        let blurred = self.separable(&gaussian_kernel(factor as f64 / 2.0));
+        Image::construct(self.width().div_ceil(factor),
+                         self.height().div_ceil(factor),
+                         |x, y| blurred[(factor * x, factor * y)])
+    }
+
+    /// Upsamples to the given size, the inverse of `pyr_down`
+    pub fn pyr_up(&self, width: usize, height: usize) -> Image {
+        Image::construct(width, height, |x, y| self.bilinear(x as f64 / 2.0, y as f64 / 2.0))
+    }
+
+    pub fn gaussian_pyramid(&self, levels: usize) -> Vec<Image> {
+        let mut pyramid = vec![self.clone()];
+        while pyramid.len() < levels {
+            let next = pyramid.last().unwrap().pyr_down();
+            pyramid.push(next);
+        }
+        pyramid
+    }
+
+    /// Band-pass levels followed by the lowest resolution residual
+    pub fn laplacian_pyramid(&self, levels: usize) -> Vec<Image> {
+        let gaussian = self.gaussian_pyramid(levels);
+        gaussian.iter()
+            .zip(gaussian.iter().skip(1))
+            .map(|(fine, coarse)| {
+                let up = coarse.pyr_up(fine.width(), fine.height());
+                fine.similar(|x, y| fine[(x, y)] - up[(x, y)])
+            })
+            .chain(gaussian.last().cloned())
+            .collect()
+    }
+
+    /// Reconstructs an image from `laplacian_pyramid` levels
+    pub fn collapse_pyramid(pyramid: Vec<Image>) -> Image {
+        pyramid.into_iter()
+            .rev()
+            .reduce(|coarse, fine| {
+                let up = coarse.pyr_up(fine.width(), fine.height());
+                fine.similar(|x, y| fine[(x, y)] + up[(x, y)])
+            })
+            .unwrap_or_else(|| Image::empty(0, 0))
+    }
+
+    /// Shrinks the image so that neither side exceeds `max_dim`, keeping the
+    /// aspect ratio. Halves with `pyr_down` first to avoid aliasing
+    pub fn thumbnail(&self, max_dim: usize) -> Image {
+        if self.width() == 0 || self.height() == 0 {
+            return self.clone();
+        }
+        let mut image = self.clone();
+        while image.width().max(image.height()) >= 2 * max_dim.max(1) {
+            image = image.pyr_down();
+        }
+        let scale = (max_dim as f64 / image.width().max(image.height()) as f64).min(1.0);
+        let width = ((image.width() as f64 * scale).round() as usize).max(1);
+        let height = ((image.height() as f64 * scale).round() as usize).max(1);
+        Image::construct(width, height, |x, y| image.bilinear(
+            (x as f64 + 0.5) / scale - 0.5,
+            (y as f64 + 0.5) / scale - 0.5,
+        ))
+    }
+
+    /// Resamples the image to `width` by `height`, aligning pixel centres.
+    /// When shrinking, halves with `pyr_down` first while both sides are at
+    /// least twice the target, to avoid aliasing
+    pub fn resize(&self, width: usize, height: usize, interpolation: Interpolation) -> Image {
+        let mut image = self.clone();
+        while image.width() >= 2 * width.max(1) && image.height() >= 2 * height.max(1) {
+            image = image.pyr_down();
+        }
+        let sx = image.width() as f64 / width as f64;
+        let sy = image.height() as f64 / height as f64;
+        Image::construct(width, height, |x, y| image.sample(
+            (x as f64 + 0.5) * sx - 0.5,
+            (y as f64 + 0.5) * sy - 0.5,
+            interpolation,
+        ))
+    }
+
+    /// A hash of the dimensions and pixels, to tell images apart cheaply
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.width(), self.height()).hash(&mut hasher);
+        self.0.iter()
+            .flatten()
+            .flat_map(|pixel| pixel.into_iter())
+            .for_each(|channel| channel.to_bits().hash(&mut hasher));
+        hasher.finish()
+    }
+
+    /// Floyd-Steinberg error diffusion: every pixel is replaced by `reduce`
+    /// of it, in reading order, and the error is pushed on to the neighbours
+    /// still to come
+    fn diffuse_error(mut self, reduce: impl Fn(Rgba) -> Rgba) -> Image {
+        let (width, height) = (self.width() as i64, self.height() as i64);
+        for y in 0..height {
+            for x in 0..width {
+                let old = self[(x as usize, y as usize)];
+                let new = reduce(old);
+                self[(x as usize, y as usize)] = new;
+                let error = old - new;
+                for (dx, dy, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || nx >= width || ny >= height {
+                        continue;
+                    }
+                    let neighbour = &mut self[(nx as usize, ny as usize)];
+                    *neighbour = *neighbour + error * (weight / 16.0);
+                }
+            }
+        }
+        self
+    }
+
+    /// The image surrounded by the given number of `color` pixels on each side
+    pub fn padded(&self, left: usize, top: usize, right: usize, bottom: usize, color: Rgba) -> Image {
+        Image::construct(self.width() + left + right, self.height() + top + bottom, |x, y| {
+            match (x.checked_sub(left), y.checked_sub(top)) {
+                (Some(x), Some(y)) if x < self.width() && y < self.height() => self[(x, y)],
+                _ => color,
+            }
+        })
+    }
+
+    /// The `width` by `height` region with its top left corner at `(x, y)`
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Image {
+        Image::construct(width, height, |i, j| self[(x + i, y + j)])
+    }
+
+    /// Memory held by the pixels of the image
+    pub fn bytes(&self) -> usize {
+        self.width() * self.height() * std::mem::size_of::<Rgba>()
+    }
+
     pub fn into_rgba8(self) -> Vec<u8> {
         self.0.into_iter()
             .flatten()
             .flat_map(Into::<[u8; 4]>::into)
             .collect()
     }
+
+    /// The pixels row by row in the given layout. With `premultiply`, the
+    /// colour channels are scaled by alpha first
+    pub fn into_bytes(self, format: PixelFormat, premultiply: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.width() * self.height() * format.bytes_per_pixel());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let pixel = self[(x, y)];
+                let pixel = if premultiply {
+                    pixel.map_color(|v| v * pixel.alpha().clamp(0.0, 1.0))
+                } else {
+                    pixel
+                };
+                let [r, g, b, a]: [u8; 4] = pixel.into();
+                match format {
+                    PixelFormat::Rgba8 => bytes.extend([r, g, b, a]),
+                    PixelFormat::Bgra8 => bytes.extend([b, g, r, a]),
+                    PixelFormat::Argb8 => bytes.extend([a, r, g, b]),
+                    PixelFormat::Rgb8 => bytes.extend([r, g, b]),
+                    PixelFormat::Gray8 => bytes.push(Into::<[u8; 4]>::into(Rgba::gray(pixel.intensity()))[0]),
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Splits the image into `tile_w`x`tile_h` tiles, row of tiles by row.
+    /// Each tile is a copy extended by `halo` pixels on every side, taken
+    /// from the neighbouring tiles or by replicating the image edges
+    pub fn tiles(&self, tile_w: usize, tile_h: usize, halo: usize) -> impl Iterator<Item = Tile> + '_ {
+        assert!(tile_w > 0 && tile_h > 0, "Tiles must not be empty");
+        (0..self.height()).step_by(tile_h)
+            .flat_map(move |y| (0..self.width()).step_by(tile_w)
+                .map(move |x| (x, y)))
+            .map(move |(x, y)| {
+                let width = tile_w.min(self.width() - x);
+                let height = tile_h.min(self.height() - y);
+                let image = Image::construct(width + 2 * halo, height + 2 * halo, |i, j| self.get(
+                    x as i64 + i as i64 - halo as i64,
+                    y as i64 + j as i64 - halo as i64,
+                    BorderMode::Replicate,
+                ));
+                Tile { x, y, width, height, halo, image }
+            })
+    }
+
+    /// Splits the image into disjoint `tile_w`x`tile_h` tiles that can be
+    /// written to independently, row of tiles by row like `tiles`
+    pub fn tiles_mut(&mut self, tile_w: usize, tile_h: usize) -> impl Iterator<Item = TileMut<'_>> {
+        assert!(tile_w > 0 && tile_h > 0, "Tiles must not be empty");
+        let mut tiles = Vec::new();
+        for (tx, chunk) in self.0.chunks_mut(tile_w).enumerate() {
+            let mut columns = chunk.iter_mut()
+                .map(|column| column.chunks_mut(tile_h))
+                .collect::<Vec<_>>();
+            for ty in 0.. {
+                let tile = columns.iter_mut()
+                    .filter_map(Iterator::next)
+                    .collect::<Vec<_>>();
+                if tile.is_empty() {
+                    break;
+                }
+                tiles.push(TileMut { x: tx * tile_w, y: ty * tile_h, columns: tile });
+            }
+        }
+        // Borrowed column by column, handed out in the order of `tiles`
+        tiles.sort_by_key(|tile| (tile.y, tile.x));
+        tiles.into_iter()
+    }
+}
+
+/// Weights of the four taps around a sample `t` past the second of them
+fn catmull_rom(t: f64) -> [f64; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        (-t3 + 2.0 * t2 - t) / 2.0,
+        (3.0 * t3 - 5.0 * t2 + 2.0) / 2.0,
+        (-3.0 * t3 + 4.0 * t2 + t) / 2.0,
+        (t3 - t2) / 2.0,
+    ]
+}
+
+/// Normalized 1D gaussian reaching out to three sigmas
+fn gaussian_kernel(sigma: f64) -> Vec<f64> {
+    let half = (3.0 * sigma).ceil() as i64;
+    let kernel = (-half..=half)
+        .map(|n| (-((n * n) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let total = kernel.iter().sum::<f64>();
+    kernel.into_iter()
+        .map(|k| k / total)
+        .collect()
+}
+
+const BINOMIAL: [f64; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+/// Mertens exposure fusion: every shot is weighted per pixel by contrast,
+/// saturation and well-exposedness, and the weighted shots are blended
+/// through Laplacian pyramids to avoid seams
+pub fn fuse_exposures(images: &[Image]) -> Image {
+    let first = images.first().expect("Expected at least one exposure");
+    let (width, height) = (first.width(), first.height());
+    assert!(images.iter().all(|i| i.width() == width && i.height() == height),
+            "All exposures must have the same dimensions");
+
+    let weights = images.iter()
+        .map(|image| image.similar(|x, y| {
+            let pixel = image[(x, y)];
+            let gray = |x: usize, y: usize| image[(x, y)].grayscale().red();
+            let xp = x.saturating_sub(1);
+            let xn = (x + 1).min(width - 1);
+            let yp = y.saturating_sub(1);
+            let yn = (y + 1).min(height - 1);
+            let contrast = (4.0 * gray(x, y) - gray(xp, y) - gray(xn, y) - gray(x, yp) - gray(x, yn)).abs();
+            let [r, g, b, _]: [f64; 4] = pixel.into();
+            let mean = (r + g + b) / 3.0;
+            let saturation = (((r - mean).powi(2) + (g - mean).powi(2) + (b - mean).powi(2)) / 3.0).sqrt();
+            let exposedness = [r, g, b].into_iter()
+                .map(|v| (-(v - 0.5).powi(2) / (2.0 * 0.2 * 0.2)).exp())
+                .product::<f64>();
+            Rgba::gray(contrast * saturation * exposedness + 1e-12)
+        }))
+        .collect::<Vec<_>>();
+
+    let levels = ((width.min(height).max(1) as f64).log2().floor() as usize).max(1);
+
+    let blended = images.iter()
+        .zip(weights.iter())
+        .map(|(image, weight)| {
+            let weight = weight.similar(|x, y| {
+                let total: f64 = weights.iter().map(|w| w[(x, y)].red()).sum();
+                Rgba::gray(weight[(x, y)].red() / total)
+            });
+            image.laplacian_pyramid(levels)
+                .into_iter()
+                .zip(weight.gaussian_pyramid(levels))
+                .map(|(band, weight)| band.similar(|x, y| band[(x, y)] * weight[(x, y)].red()))
+                .collect::<Vec<_>>()
+        })
+        .reduce(|acc, pyramid| acc.into_iter()
+            .zip(pyramid)
+            .map(|(a, b)| a.similar(|x, y| a[(x, y)] + b[(x, y)]))
+            .collect())
+        .unwrap();
+
+    Image::collapse_pyramid(blended)
+}
+
+/// Estimates the integer translation `(dx, dy)` such that
+/// `frame[(x + dx, y + dy)]` lines up with `reference[(x, y)]`
+pub fn phase_correlation(reference: &Image, frame: &Image) -> (i64, i64) {
+    let width = reference.width().max(frame.width()).next_power_of_two();
+    let height = reference.height().max(frame.height()).next_power_of_two();
+    let spectrum = |image: &Image| {
+        let mut data = (0..width)
+            .map(|x| (0..height)
+                .map(|y| if x < image.width() && y < image.height() {
+                    image[(x, y)].grayscale().red().into()
+                } else {
+                    Complex::ZERO
+                })
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        fft2d(&mut data, false);
+        data
+    };
+
+    let a = spectrum(reference);
+    let b = spectrum(frame);
+    let mut cross = a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| {
+                let product = b.conj() * *a;
+                product.scale(1.0 / product.norm().max(1e-12))
+            })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    fft2d(&mut cross, true);
+
+    let (peak_x, peak_y, _) = cross.iter()
+        .enumerate()
+        .flat_map(|(x, column)| column.iter()
+            .enumerate()
+            .map(move |(y, c)| (x, y, c.re)))
+        .fold((0, 0, f64::MIN), |best, candidate| if candidate.2 > best.2 { candidate } else { best });
+
+    let wrap = |peak: usize, size: usize| if peak > size / 2 {
+        peak as i64 - size as i64
+    } else {
+        peak as i64
+    };
+    (-wrap(peak_x, width), -wrap(peak_y, height))
+}
+
+/// Registers every frame against the first via phase correlation and
+/// stacks the aligned frames to reduce noise
+pub fn average_aligned(frames: &[Image], stacking: Stacking) -> Image {
+    let reference = frames.first().expect("Expected at least one frame");
+    let aligned = frames.iter()
+        .map(|frame| {
+            let (dx, dy) = phase_correlation(reference, frame);
+            reference.similar(|x, y| frame[(
+                (x as i64 + dx).clamp(0, frame.width() as i64 - 1) as usize,
+                (y as i64 + dy).clamp(0, frame.height() as i64 - 1) as usize,
+            )])
+        })
+        .collect::<Vec<_>>();
+
+    reference.similar(|x, y| {
+        let pixels = aligned.iter()
+            .map(|frame| frame[(x, y)])
+            .collect::<Vec<_>>();
+        match stacking {
+            Stacking::Mean => pixels.iter()
+                .fold(Rgba::gray(0.0).with_alpha(0.0), |acc, p| acc + *p) / pixels.len() as f64,
+            Stacking::Median => Rgba::median(&pixels),
+        }
+    })
+}
+
+/// Tiles thumbnails of `images` into a grid with `columns` cells per row,
+/// each thumbnail centred in a `thumb_size` square cell. With `labels`, one
+/// per image, every cell gets a caption below it, cut to the cell's width
+pub fn make_contact_sheet(images: &[Image], columns: usize, thumb_size: usize, labels: Option<&[String]>) -> Image {
+    const SPACING: usize = 4;
+    const TEXT_SCALE: usize = 2;
+    const CAPTION: usize = GLYPH_HEIGHT * TEXT_SCALE + 2;
+    if let Some(labels) = labels {
+        assert_eq!(labels.len(), images.len(), "Expected one label per image");
+    }
+    let columns = columns.max(1);
+    let rows = images.len().div_ceil(columns);
+    let caption = if labels.is_some() { CAPTION } else { 0 };
+    let (cell_width, cell_height) = (thumb_size + SPACING, thumb_size + caption + SPACING);
+    let mut sheet = Image::empty(columns * cell_width + SPACING, rows * cell_height + SPACING);
+    if thumb_size == 0 {
+        return sheet;
+    }
+    for (n, image) in images.iter().enumerate() {
+        let (cell_left, cell_top) = (SPACING + (n % columns) * cell_width, SPACING + (n / columns) * cell_height);
+        let thumb = image.thumbnail(thumb_size);
+        let left = cell_left + (thumb_size - thumb.width()) / 2;
+        let top = cell_top + (thumb_size - thumb.height()) / 2;
+        for x in 0..thumb.width() {
+            for y in 0..thumb.height() {
+                sheet[(left + x, top + y)] = thumb[(x, y)];
+            }
+        }
+        if let Some(labels) = labels {
+            let fitting = (thumb_size + TEXT_SCALE) / ((GLYPH_WIDTH + 1) * TEXT_SCALE);
+            let label = labels[n].chars().take(fitting).collect::<String>();
+            let left = cell_left + (thumb_size - text_width(&label, TEXT_SCALE)) / 2;
+            draw_text(&mut sheet, &label, left, cell_top + thumb_size + 1, TEXT_SCALE, Rgba::WHITE);
+        }
+    }
+    sheet
 }
 
 impl From<RgbaImage> for Image {
@@ -99,9 +755,140 @@ impl Into<RgbaImage> for Image {
     }
 }
 
+/// A copy of part of an image, see [`Image::tiles`]
+#[derive(Clone)]
+pub struct Tile {
+    /// Position of the tile's top left corner in the source image
+    pub x: usize,
+    pub y: usize,
+    /// Size of the tile without the halo
+    pub width: usize,
+    pub height: usize,
+    pub halo: usize,
+    /// The tile with its halo, so the source pixel `(x, y)` is at `(halo, halo)`
+    pub image: Image,
+}
+
+impl Tile {
+    /// The tile without its halo
+    pub fn core(&self) -> Image {
+        Image::construct(self.width, self.height, |i, j| self.image[(i + self.halo, j + self.halo)])
+    }
+}
+
+/// A mutable view of part of an image, see [`Image::tiles_mut`].
+/// Indexed relative to the tile's top left corner
+pub struct TileMut<'a> {
+    pub x: usize,
+    pub y: usize,
+    columns: Vec<&'a mut [Rgba]>,
+}
+
+impl TileMut<'_> {
+    pub fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.columns.first()
+            .map(|v| v.len())
+            .unwrap_or(0)
+    }
+}
+
+impl std::ops::Index<(usize, usize)> for TileMut<'_> {
+    type Output = Rgba;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        &self.columns[x][y]
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for TileMut<'_> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        &mut self.columns[x][y]
+    }
+}
+
+/// Per-channel statistics of an image, as returned by `Image::stats`
+#[derive(Copy, Clone, Debug)]
+pub struct ImageStats {
+    pub min: Rgba,
+    pub max: Rgba,
+    pub mean: Rgba,
+    /// Population standard deviation
+    pub std_dev: Rgba,
+}
+
+/// Measurements of a single pipeline stage
+#[derive(Copy, Clone, Debug)]
+pub struct StageProfile {
+    pub duration: Duration,
+    /// Size of the image the stage received
+    pub input_bytes: usize,
+    /// Size of the image the stage produced
+    pub output_bytes: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PipelineProfile {
+    pub stages: Vec<StageProfile>,
+}
+
+impl PipelineProfile {
+    pub fn total_duration(&self) -> Duration {
+        self.stages.iter()
+            .map(|s| s.duration)
+            .sum()
+    }
+
+    /// Bytes of intermediate images allocated over the whole run
+    pub fn allocated_bytes(&self) -> usize {
+        self.stages.iter()
+            .map(|s| s.output_bytes)
+            .sum()
+    }
+
+    /// The largest input and output held at once by a stage. Stages allocate
+    /// scratch images of their own too, so this is a lower bound
+    pub fn peak_bytes(&self) -> usize {
+        self.stages.iter()
+            .map(|s| s.input_bytes + s.output_bytes)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
 #[derive(Default)]
 pub struct CpuPipeline {
-    actions: Vec<Box<dyn FnOnce(Image) -> Image>>
+    actions: Vec<Box<dyn FnOnce(Image) -> Image>>,
+    weights: Option<Rc<Image>>,
+    border: BorderMode,
+    output_size: OutputSize,
+    checks: Vec<Check>,
+}
+
+type Issue = Option<(Severity, String)>;
+
+/// Validation of a single stage, see `Pipeline::validate`
+enum Check {
+    /// A possible problem with the stage at the given index
+    Issue(usize, Box<dyn Fn(&ImageInfo) -> Issue>),
+    /// The preceding stage changes the size of the image
+    Reshape(Box<dyn Fn(ImageInfo) -> ImageInfo>),
+}
+
+/// Rejects frequencies that are not above zero
+fn positive_cutoff(cutoff: f64) -> impl Fn(&ImageInfo) -> Issue {
+    move |_| (cutoff <= 0.0)
+        .then(|| (Severity::Error, format!("Cutoff frequency {cutoff} is not positive")))
+}
+
+/// Warns about a window that does not fit in the image
+fn fits_in_image(what: &'static str, width: usize, height: usize) -> impl Fn(&ImageInfo) -> Issue {
+    move |info| (width > info.width || height > info.height)
+        .then(|| (Severity::Warning, format!("{what} of {width}x{height} is larger than the {}x{} image",
+                                             info.width, info.height)))
 }
 
 impl CpuPipeline {
@@ -110,6 +897,140 @@ impl CpuPipeline {
         self
     }
 
+    /// Adds a check for the next stage to `validate`
+    fn check(mut self, check: impl Fn(&ImageInfo) -> Issue + 'static) -> Self {
+        let stage = self.actions.len();
+        self.checks.push(Check::Issue(stage, Box::new(check)));
+        self
+    }
+
+    /// Warns in `validate` when the next stage is given anything but `accepted`
+    fn expects(self, stage: &'static str, accepted: &'static [Content]) -> Self {
+        self.check(move |info| (!accepted.contains(&info.content))
+            .then(|| (Severity::Warning, format!("{stage} expects {accepted:?}, but is given {:?}", info.content))))
+    }
+
+    /// Tells `validate` what the last stage's output stands for
+    fn produces(self, content: Content) -> Self {
+        self.reshape(move |info| ImageInfo { content, ..info })
+    }
+
+    /// Tells `validate` how the last stage changes the size of the image
+    fn reshape(mut self, reshape: impl Fn(ImageInfo) -> ImageInfo + 'static) -> Self {
+        self.checks.push(Check::Reshape(Box::new(reshape)));
+        self
+    }
+
+    /// Adds a filter with a `width` by `height` window, built by `stage` with
+    /// the same output size, resized according to `output_size`
+    fn windowed(self, width: usize, height: usize, stage: impl FnOnce(Self) -> Self) -> Self {
+        let (ax, ay) = Anchor::Center.resolve(width, height);
+        let (grow_x, grow_y) = (width.saturating_sub(1), height.saturating_sub(1));
+        // Both keep the region from the anchor on, one window less than the input
+        let trim = move |image: Image| image.crop(ax as usize, ay as usize,
+                                                  image.width().saturating_sub(grow_x),
+                                                  image.height().saturating_sub(grow_y));
+        match self.output_size {
+            OutputSize::Same => stage(self),
+            OutputSize::Valid => stage(self)
+                .commit(trim)
+                .reshape(move |info| ImageInfo {
+                    width: info.width.saturating_sub(grow_x),
+                    height: info.height.saturating_sub(grow_y),
+                    ..info
+                }),
+            OutputSize::Full => {
+                let border = self.border;
+                // The stage's own checks see the padded image it will run on
+                let padded = self.commit(move |image| Image::construct(
+                        image.width() + 2 * grow_x,
+                        image.height() + 2 * grow_y,
+                        |x, y| image.get(x as i64 - grow_x as i64, y as i64 - grow_y as i64, border)))
+                    .reshape(move |info| ImageInfo {
+                        width: info.width + 2 * grow_x,
+                        height: info.height + 2 * grow_y,
+                        ..info
+                    });
+                stage(padded)
+                    .commit(trim)
+                    .reshape(move |info| ImageInfo {
+                        width: info.width - grow_x,
+                        height: info.height - grow_y,
+                        ..info
+                    })
+            }
+        }
+    }
+
+    /// Scales the image's spectrum by `gain` of the signed frequency,
+    /// keeping the alpha channel of the input
+    fn frequency_filter(self, gain: impl Fn(f64, f64) -> f64 + 'static) -> Self {
+        self.commit(move |image| {
+            let mut spectrum = Spectrum::forward(&image);
+            spectrum.filter(gain);
+            let filtered = spectrum.inverse();
+            image.similar(|x, y| filtered[(x, y)].with_alpha(image[(x, y)].alpha()))
+        })
+    }
+
+    /// Like `apply`, but also measures every stage
+    pub fn apply_profiled(self, image: &Image) -> (Image, PipelineProfile) {
+        let mut stages = vec![];
+        let out = self.actions.into_iter()
+            .fold(image.clone(), |image, f| {
+                let input_bytes = image.bytes();
+                let start = Instant::now();
+                let out = f(image);
+                stages.push(StageProfile {
+                    duration: start.elapsed(),
+                    input_bytes,
+                    output_bytes: out.bytes(),
+                });
+                out
+            });
+        (out, PipelineProfile { stages })
+    }
+
+    /// Binarizes against a threshold computed from the mean and standard
+    /// deviation of the intensities in the `size` window around each pixel
+    fn local_threshold(self, size: usize, threshold: impl Fn(f64, f64) -> f64 + 'static) -> Self {
+        self.check(fits_in_image("Threshold window", size, size))
+            .commit(move |image| {
+            let gray = image.similar(|x, y| Rgba::gray(image[(x, y)].intensity()));
+            let (sums, squares) = (IntegralImage::new(&gray), IntegralImage::squares(&gray));
+            image.similar(|x, y| {
+                let mean = sums.mean(x, y, size / 2).red();
+                let deviation = sums.variance(&squares, x, y, size / 2).red().sqrt();
+                if gray[(x, y)].red() > threshold(mean, deviation) {
+                    Rgba::WHITE
+                } else {
+                    Rgba::BLACK
+                }
+            })
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    /// Scales the colour channels so that `reference` gives the same value
+    /// for all of them, keeping the mean of the three
+    fn white_balance(self, reference: impl Fn(&[f64]) -> f64 + 'static) -> Self {
+        self.commit(move |image| {
+            let references = (0..3)
+                .map(|c| reference(&image.0.iter()
+                    .flatten()
+                    .filter_map(|pixel| pixel.into_iter().nth(c))
+                    .collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            let gray = references.iter().sum::<f64>() / 3.0;
+            let gains = references.iter()
+                .map(|r| if *r > 0.0 { gray / r } else { 1.0 })
+                .chain([1.0])
+                .collect::<Rgba>();
+            image.similar(|x, y| (image[(x, y)] * gains)
+                .map_color(|v| v.clamp(0.0, 1.0)))
+        })
+    }
+
     fn dbg(self, loc: impl AsRef<Path> + 'static) -> Self {
         self.commit(move |image| {
             image.save(loc).unwrap();
@@ -117,11 +1038,14 @@ impl CpuPipeline {
         })
     }
 
-    fn convolve(self,
+    fn convolve_fn(self,
                 needle_width: usize,
                 needle_height: usize,
+                anchor: Anchor,
                 needle: impl Fn(usize, usize) -> Rgba + 'static,
                 f: impl Fn(Self, Self) -> Self + 'static) -> Self {
+        let (ax, ay) = anchor.resolve(needle_width, needle_height);
+        let border = self.border;
         self.commit(move |image| {
             let out = (0..needle_width)
                 .flat_map(|x| (0..needle_height)
@@ -130,9 +1054,9 @@ impl CpuPipeline {
                     let needle_pixel = needle (x, y);
                     let image = image.clone();
                     CpuPipeline::default()
+                        .border(border)
                         .commit(|_| image)
-                        .offset(x as i64 - needle_width as i64 >> 1,
-                                y as i64 - needle_height as i64 >> 1)
+                        .offset(x as i64 - ax, y as i64 - ay)
                         .dim(needle_pixel)
                 })
                 .fold(CpuPipeline::default(), f);
@@ -140,53 +1064,192 @@ impl CpuPipeline {
         })
     }
 
-    fn convolve_by(self, needle: Image, f: impl Fn(Self, Self) -> Self + 'static) -> Self {
-        self.convolve(needle.width(),
-                      needle.height(),
-                      move |x, y| needle[(x, y)],
-                      f
+    fn convolve_by(self, needle: Image, anchor: Anchor, f: impl Fn(Self, Self) -> Self + 'static) -> Self {
+        self.convolve_fn(needle.width(),
+                         needle.height(),
+                         anchor,
+                         move |x, y| needle[(x, y)],
+                         f
         )
     }
 }
 
 pub struct CpuGenerator {
     pub size: usize,
+    /// Whether blur kernels are scaled to sum to one, so that they keep the
+    /// overall brightness. On by default
+    pub normalize: bool,
 }
 
 impl CpuGenerator {
     pub fn new(size: usize) -> Self {
         CpuGenerator {
             size,
+            normalize: true,
+        }
+    }
+
+    /// Leaves blur kernels with their raw weights
+    pub fn unnormalized(self) -> Self {
+        CpuGenerator {
+            normalize: false,
+            ..self
         }
     }
 }
 
-impl Generator for CpuGenerator {
-    type Pipeline = CpuPipeline;
+/// Scales the colour channels of a kernel so that its weights sum to one.
+/// Kernels summing to zero, such as derivatives, are left as they are
+fn normalize_kernel(kernel: Image) -> Image {
+    let total = kernel.0.iter()
+        .flatten()
+        .map(|w| w.red())
+        .sum::<f64>();
+    if total.abs() < f64::EPSILON {
+        return kernel;
+    }
+    kernel.similar(|x, y| kernel[(x, y)].map_color(|c| c / total))
+}
 
-    fn gaussian_noise(&self, mean: f64, variance: f64, intensity: f64) -> Self::Pipeline {
-        let pdf = Gaussian::new(mean, variance);
-        CpuPipeline::default()
-            .commit(move |image| {
-                image.similar(|_, _| {
-                    let rand = thread_rng().gen_range(0..255u8) as f64 / 256.0;
-                    let res = pdf.density(rand) * intensity;
-                    Rgba::gray(0.5 + if thread_rng().gen() {
-                        res
-                    } else {
-                        -res
-                    }).into()
-                })
-            })
+impl Filter<CpuPipeline> {
+    /// The same filter with its kernel scaled to sum to one, see `CpuGenerator::normalize`.
+    /// Only convolutions have a kernel, other filters are returned as they are
+    pub fn normalized(self) -> Self {
+        match self {
+            Filter::Convoluted(n) => {
+                let kernel = normalize_kernel(n.generate(0, 0));
+                Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel))
+            }
+            other => other,
+        }
     }
+}
 
-    fn salt_and_pepper_noise(&self, variance: f64) -> Self::Pipeline {
-        let pdf = Gaussian::new(0.5, variance);
-        CpuPipeline::default()
-            .commit(move |image| image.similar(|_, _| {
-                let rand = thread_rng().gen_range(0..255u8) as f64 / 256.0;
-                Rgba::gray(if pdf.density(rand) > 0.6 {
-                    if thread_rng().gen() {
+/// A 3x3 derivative kernel: `diff` across `axis`, `smooth` along the other one
+fn derivative_needle(smooth: [f64; 3], diff: [f64; 3], horizontal: bool) -> Filter<CpuPipeline> {
+    let weights = (0..3)
+        .map(|y| (0..3)
+            .map(|x| if horizontal {
+                smooth[y] * diff[x]
+            } else {
+                smooth[x] * diff[y]
+            })
+            .collect())
+        .collect::<Vec<_>>();
+    let kernel = Image::from_weights(&weights);
+    Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel))
+}
+
+const DIFFERENCE: [f64; 3] = [-1.0, 0.0, 1.0];
+
+/// Row-major weights of a `size` square gaussian, differentiated `x_order`
+/// times along x and `y_order` times along y, each order being at most two
+pub(crate) fn gaussian_derivative_weights(size: usize, sigma: f64, x_order: usize, y_order: usize) -> Vec<Vec<f64>> {
+    let center = (size / 2) as f64;
+    let gauss = |v: f64| (-v * v / (2.0 * sigma * sigma)).exp();
+    let total = (0..size)
+        .map(|n| gauss(n as f64 - center))
+        .sum::<f64>();
+    // Derivatives of the 1D gaussian, with the sign of `DIFFERENCE` for the first
+    let derivative = |v: f64, order: usize| match order {
+        0 => 1.0,
+        1 => v / (sigma * sigma),
+        _ => (v * v / (sigma * sigma) - 1.0) / (sigma * sigma),
+    } * gauss(v) / total;
+    (0..size)
+        .map(|y| (0..size)
+            .map(|x| derivative(x as f64 - center, x_order) * derivative(y as f64 - center, y_order))
+            .collect())
+        .collect()
+}
+
+impl Generator for CpuGenerator {
+    type Pipeline = CpuPipeline;
+
+    fn sobel_x(&self) -> Filter<Self::Pipeline> {
+        derivative_needle([1.0, 2.0, 1.0], DIFFERENCE, true)
+    }
+
+    fn sobel_y(&self) -> Filter<Self::Pipeline> {
+        derivative_needle([1.0, 2.0, 1.0], DIFFERENCE, false)
+    }
+
+    fn prewitt_x(&self) -> Filter<Self::Pipeline> {
+        derivative_needle([1.0, 1.0, 1.0], DIFFERENCE, true)
+    }
+
+    fn prewitt_y(&self) -> Filter<Self::Pipeline> {
+        derivative_needle([1.0, 1.0, 1.0], DIFFERENCE, false)
+    }
+
+    fn scharr_x(&self) -> Filter<Self::Pipeline> {
+        derivative_needle([3.0, 10.0, 3.0], DIFFERENCE, true)
+    }
+
+    fn scharr_y(&self) -> Filter<Self::Pipeline> {
+        derivative_needle([3.0, 10.0, 3.0], DIFFERENCE, false)
+    }
+
+    fn laplacian(&self) -> Filter<Self::Pipeline> {
+        let kernel = Image::from_weights(&[
+            vec![0.0, 1.0, 0.0],
+            vec![1.0, -4.0, 1.0],
+            vec![0.0, 1.0, 0.0],
+        ]);
+        Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel))
+    }
+
+    fn gaussian_derivative_needle(&self, sigma: f64, order: usize, axis: Axis) -> Filter<Self::Pipeline> {
+        assert!(order <= 2, "Only derivatives up to the second order are supported, got {order}");
+        let (x, y) = match axis {
+            Axis::X => (order, 0),
+            Axis::Y => (0, order),
+        };
+        let kernel = Image::from_weights(&gaussian_derivative_weights(self.size, sigma, x, y));
+        Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel))
+    }
+
+    fn log_needle(&self, sigma: f64) -> Filter<Self::Pipeline> {
+        let center = (self.size / 2) as f64;
+        let weights = (0..self.size)
+            .map(|y| (0..self.size)
+                .map(|x| {
+                    let r2 = ((x as f64 - center).powi(2) + (y as f64 - center).powi(2)) / (2.0 * sigma * sigma);
+                    -(1.0 - r2) * (-r2).exp() / (PI * sigma.powi(4))
+                })
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mean = weights.iter().flatten().sum::<f64>() / (self.size * self.size) as f64;
+        let weights = weights.into_iter()
+            .map(|row| row.into_iter().map(|w| w - mean).collect())
+            .collect::<Vec<_>>();
+        let kernel = Image::from_weights(&weights);
+        Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel))
+    }
+
+    fn gaussian_noise(&self, mean: f64, variance: f64, intensity: f64) -> Self::Pipeline {
+        let pdf = Gaussian::new(mean, variance);
+        CpuPipeline::default()
+            .commit(move |image| {
+                image.similar(|_, _| {
+                    let rand = thread_rng().gen_range(0..255u8) as f64 / 256.0;
+                    let res = pdf.density(rand) * intensity;
+                    Rgba::gray(0.5 + if thread_rng().gen() {
+                        res
+                    } else {
+                        -res
+                    }).into()
+                })
+            })
+    }
+
+    fn salt_and_pepper_noise(&self, variance: f64) -> Self::Pipeline {
+        let pdf = Gaussian::new(0.5, variance);
+        CpuPipeline::default()
+            .commit(move |image| image.similar(|_, _| {
+                let rand = thread_rng().gen_range(0..255u8) as f64 / 256.0;
+                Rgba::gray(if pdf.density(rand) > 0.6 {
+                    if thread_rng().gen() {
                         1.0
                     } else {
                         0.0
@@ -208,21 +1271,56 @@ impl Generator for CpuGenerator {
             pixel.into())))
     }
 
-    fn gaussian_needle(&self, variance: f64) -> Filter<Self::Pipeline> {
+    fn disk_needle(&self, radius: f64) -> Filter<Self::Pipeline> {
+        // An empty kernel, which `filter` reports as an error
+        if radius <= 0.0 {
+            return Filter::Custom(vec![]);
+        }
+        let half = radius.ceil() as usize;
+        let size = 2 * half + 1;
+        // Fraction of each pixel inside the circle, from a 4x4 grid of samples
+        let coverage = |x: usize, y: usize| {
+            let inside = (0..16)
+                .filter(|n| {
+                    let dx = x as f64 - half as f64 + (n % 4) as f64 / 4.0 - 0.375;
+                    let dy = y as f64 - half as f64 + (n / 4) as f64 / 4.0 - 0.375;
+                    dx * dx + dy * dy <= radius * radius
+                })
+                .count();
+            inside as f64 / 16.0
+        };
+        let weights = (0..size)
+            .map(|y| (0..size).map(|x| coverage(x, y)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let total = weights.iter().flatten().sum::<f64>();
+        let weights = weights.into_iter()
+            .map(|row| row.into_iter().map(|w| w / total).collect())
+            .collect::<Vec<_>>();
+        let kernel = Image::from_weights(&weights);
+        Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel))
+    }
+
+    fn gaussian_needle(&self, sigma: f64) -> Filter<Self::Pipeline> {
         let size = self.size;
-        Filter::Convoluted(CpuPipeline::default()
+        let normalize = self.normalize;
+        let needle = Filter::Convoluted(CpuPipeline::default()
             .commit(move |_| Image::construct(size, size, |i, j| {
                 let center = (size >> 1) as i64;
                 let i = (i as i64 - center).abs();
                 let j = (j as i64 - center).abs();
                 let offset = (i * i + j * j) as f64;
-                let gauss = Gaussian::new(0f64, variance)
+                let gauss = Gaussian::new(0f64, sigma)
                     .density(offset.sqrt());
 
                 let rgba = Rgba::gray(gauss);
                 let out = rgba.into();
                 out
-            })))
+            })));
+        if normalize {
+            needle.normalized()
+        } else {
+            needle
+        }
     }
 }
 
@@ -230,6 +1328,10 @@ impl super::pipeline::Image for Image {
     fn black(width: usize, height: usize) -> Self {
         Image::from_pixel(width, height, Rgba::BLACK.into())
     }
+
+    fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> Rgba) -> Self {
+        Image::construct(width, height, f)
+    }
 }
 
 fn image_by(op: &'static (dyn Fn(Rgba, Rgba) -> Rgba + 'static)) -> impl Fn(CpuPipeline, CpuPipeline) -> CpuPipeline {
@@ -251,17 +1353,63 @@ impl Pipeline for CpuPipeline {
     fn filter(self, needle: Filter<Self>) -> Self {
         match needle {
             Filter::Convoluted(n) => {
-                self.convolve_by(n.generate(0, 0), Self::add)
+                let kernel = n.generate(0, 0);
+                self.windowed(kernel.width(), kernel.height(), move |this| {
+                    let this = this.check(fits_in_image("Kernel", kernel.width(), kernel.height()));
+                    let border = this.border;
+                    match this.weights.clone() {
+                        Some(weights) => this.commit(move |image| image.weighted_convolve(&kernel, &weights, border)),
+                        None => this.convolve_by(kernel, Anchor::Center, Self::add),
+                    }
+                })
             }
-            Filter::Median(size) => {
-                self.commit(move |image| {
+            Filter::Custom(rows) => {
+                let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+                let ragged = rows.iter().any(|row| row.len() != width);
+                let empty = width == 0;
+                // Short rows are padded with zeros so that the kernel can still be built
+                let rows = rows.into_iter()
+                    .map(|mut row| {
+                        row.resize(width, 0.0);
+                        row
+                    })
+                    .collect::<Vec<_>>();
+                let kernel = Image::from_weights(&rows);
+                self.check(move |_| (empty || ragged)
+                        .then(|| (Severity::Error, "Custom kernel rows must be non-empty and of equal length".to_string())))
+                    .filter(Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel)))
+            }
+            Filter::Median(size) => self.windowed(size, size, move |this| {
+                let this = this
+                    .check(move |_| (size == 0)
+                        .then(|| (Severity::Error, "Median window is empty".to_string())))
+                    .check(fits_in_image("Median window", size, size));
+                let border = this.border;
+                this.commit(move |image| {
+                    let (ax, ay) = Anchor::Center.resolve(size, size);
+                    image.similar(|x, y| {
+                        let window = (0..size as i64)
+                            .flat_map(|i| (0..size as i64)
+                                .map(move |j| (i, j)))
+                            .map(|(i, j)| image.get(x as i64 + i - ax, y as i64 + j - ay, border))
+                            .collect::<Vec<_>>();
+                        Rgba::median(&window)
+                    })
+                })
+            }),
+            Filter::Midpoint(size) => self.windowed(size, size, move |this| {
+                let this = this.check(fits_in_image("Midpoint window", size, size));
+                let border = this.border;
+                this.commit(move |image| {
                     let needle = Image::from_pixel(size, size,
                                                    Rgba::WHITE.into());
                     let min = CpuPipeline::default()
-                        .convolve_by(needle.clone(), image_by(&Rgba::min))
+                        .border(border)
+                        .convolve_by(needle.clone(), Anchor::Center, image_by(&Rgba::min))
                         .apply(&image);
                     let max = CpuPipeline::default()
-                        .convolve_by(needle, image_by(&Rgba::max))
+                        .border(border)
+                        .convolve_by(needle, Anchor::Center, image_by(&Rgba::max))
                         .apply(&image);
                     image.similar(|x, y| {
                         let min = Rgba::from(min[(x, y)]);
@@ -270,29 +1418,59 @@ impl Pipeline for CpuPipeline {
                         (min + max).into()
                     })
                 })
-            }
+            }),
         }
     }
 
     fn offset(self, x: i64, y: i64) -> Self {
-        self.commit(move |image| image.similar(|i, j| image[(
-                (i as i64 + x).min(image.width()  as i64 - 1).max(0) as usize,
-                (j as i64 + y).min(image.height() as i64 - 1).max(0) as usize,
-            )].clone()))
+        let border = self.border;
+        self.commit(move |image| image.similar(|i, j| image.get(i as i64 + x, j as i64 + y, border)))
+    }
+
+    fn border(mut self, mode: BorderMode) -> Self {
+        self.border = mode;
+        self
+    }
+
+    fn output_size(mut self, mode: OutputSize) -> Self {
+        self.output_size = mode;
+        self
+    }
+
+    fn with_weights(mut self, weights: Image) -> Self {
+        self.weights = Some(Rc::new(weights));
+        self
     }
 
     fn add(self, other: Self) -> Self {
+        let weights = self.weights.clone();
         self.commit(move |image| {
             let other = other.apply(&image);
             image.similar(|x, y| {
                 let other = Rgba::from(other[(x, y)]);
                 let this = Rgba::from(image[(x, y)]);
-                (this + other).into()
+                match &weights {
+                    Some(weights) => this + other * weights.weight(x, y),
+                    None => this + other,
+                }
+            })
+        })
+    }
+
+    fn blend(self, other: Self, amount: f64) -> Self {
+        let weights = self.weights.clone();
+        self.commit(move |image| {
+            let other = other.apply(&image);
+            image.similar(|x, y| {
+                let amount = amount * weights.as_ref()
+                    .map(|weights| weights.weight(x, y))
+                    .unwrap_or(1.0);
+                image[(x, y)] * (1.0 - amount) + other[(x, y)] * amount
             })
         })
     }
 
-    fn ennoise(self, noise: Self) -> Self {
+    fn ennoise_with(self, noise: Self, channels: NoiseChannels, mask: Option<Image>) -> Self {
         self.commit(move |image| {
             let other = noise.apply(&image);
             image.similar(|x, y| {
@@ -300,8 +1478,23 @@ impl Pipeline for CpuPipeline {
                 let this = Rgba::from(image[(x, y)]);
 
                 let noise = (noise - Rgba::gray(0.5)) * Rgba::gray(2.0);
+                let weight = mask.as_ref()
+                    .map(|mask| mask[(x, y)].intensity())
+                    .unwrap_or(1.0);
+
+                let noise = match channels {
+                    NoiseChannels::All => noise,
+                    NoiseChannels::Luminance => {
+                        Rgba::gray(noise.intensity())
+                            .with_alpha(0.0)
+                    },
+                    NoiseChannels::Only { red, green, blue } => {
+                        let pick = |keep: bool, value: f64| if keep { value } else { 0.0 };
+                        (pick(red, noise.red()), pick(green, noise.green()), pick(blue, noise.blue()), 0.0).into()
+                    },
+                };
 
-                (this + noise).into()
+                this + noise * weight
             })
         })
     }
@@ -319,6 +1512,18 @@ impl Pipeline for CpuPipeline {
             .commit(move |image| image.similar(|x, y|
                 image[(x, y)].grayscale()
             ))
+            .produces(Content::Grayscale)
+    }
+
+    fn sepia(self) -> Self {
+        const SEPIA: [[f64; 3]; 3] = [
+            [0.393, 0.769, 0.189],
+            [0.349, 0.686, 0.168],
+            [0.272, 0.534, 0.131],
+        ];
+        self.commit(|image| image.similar(|x, y| image[(x, y)]
+            .transform(SEPIA)
+            .map_color(|v| v.clamp(0.0, 1.0))))
     }
 
     fn invert(self) -> Self {
@@ -327,22 +1532,50 @@ impl Pipeline for CpuPipeline {
         }))
     }
 
-    fn gradient(self) -> Self {
-        self.convolve(3, 3,
-            |x, y| {
-                let out = match (x, y) {
-                    (0, 0) | (1, 1) | (2, 2) | (0, 2) | (2, 0) => Rgba::BLACK,
-                    (1, 0) | (0, 1) => Rgba::WHITE.map(|x| -x),
-                    (1, 2) | (2, 1) => Rgba::WHITE,
-                    (x, y) => panic!("Got invalid index (x = {x}, y = {y})")
-                };
-                out
-            },
-            image_by(&std::ops::Add::add)
-        ).commit(|image| image.similar(|x, y| {
-            let pixel = image[(x, y)].map(f64::abs);
-            pixel
+    fn gradient_magnitude(self, norm: GradientNorm, normalize: bool) -> Self {
+        // Central differences of values in [0, 1] are bounded by 1 per axis
+        let max = match (&norm, normalize) {
+            (_, false) => 1.0,
+            (GradientNorm::L1, true) => 2.0,
+            (GradientNorm::L2, true) => std::f64::consts::SQRT_2,
+        };
+        self.commit(move |image| image.similar(|x, y| {
+            let xp = x.saturating_sub(1);
+            let xn = (x + 1).min(image.width() - 1);
+            let yp = y.saturating_sub(1);
+            let yn = (y + 1).min(image.height() - 1);
+            let dx: [f64; 4] = (image[(xn, y)] - image[(xp, y)]).into();
+            let dy: [f64; 4] = (image[(x, yn)] - image[(x, yp)]).into();
+            let magnitude = |c: usize| match norm {
+                GradientNorm::L1 => dx[c].abs() + dy[c].abs(),
+                GradientNorm::L2 => dx[c].hypot(dy[c]),
+            } / max;
+            (magnitude(0), magnitude(1), magnitude(2), image[(x, y)].alpha()).into()
         }))
+        .produces(Content::GradientMagnitude)
+    }
+
+    fn collect_edges(self, image: &Image, threshold: f64) -> Vec<(usize, usize, f64)> {
+        let out = self.apply(image);
+        (0..out.height())
+            .flat_map(|y| (0..out.width())
+                .map(move |x| (x, y)))
+            .map(|(x, y)| (x, y, out[(x, y)].intensity()))
+            .filter(|(_, _, strength)| *strength > threshold)
+            .collect()
+    }
+
+    fn validate(&self, info: &ImageInfo) -> Vec<ValidationIssue> {
+        let mut info = *info;
+        let mut issues = vec![];
+        for check in &self.checks {
+            match check {
+                Check::Issue(stage, check) => issues.extend(check(&info)
+                    .map(|(severity, message)| ValidationIssue { stage: *stage, severity, message })),
+                Check::Reshape(reshape) => info = reshape(info),
+            }
+        }
+        issues
     }
 
     fn apply(self, image: &Self::Image) -> Self::Image {
@@ -350,6 +1583,20 @@ impl Pipeline for CpuPipeline {
             .fold(image.clone(), |image, f| f(image))
     }
 
+    fn when(self, condition: impl Fn(usize, usize) -> bool + 'static, stage: Self) -> Self {
+        self.commit(move |image| if condition(image.width(), image.height()) {
+            stage.apply(&image)
+        } else {
+            image
+        })
+    }
+
+    fn blur_if_larger_than(self, megapixels: f64, sigma: f64) -> Self {
+        let size = 2 * (3.0 * sigma).ceil() as usize + 1;
+        self.when(move |width, height| (width * height) as f64 > megapixels * 1e6,
+                  CpuPipeline::default().gaussian_blur(size, sigma * sigma))
+    }
+
     fn sub(self, other: Self) -> Self {
         self.commit(move |image| {
             let other = other.apply(&image);
@@ -363,8 +1610,26 @@ impl Pipeline for CpuPipeline {
         })
     }
 
+    fn diff(self, other: Self, amplify: f64, colorize: bool) -> Self {
+        self.commit(move |image| {
+            let other = other.apply(&image);
+            image.similar(|x, y| {
+                let this = image[(x, y)];
+                let difference = (this - other[(x, y)])
+                    .map_color(|v| (v.abs() * amplify).min(1.0));
+                let difference = if colorize {
+                    Rgba::from_hsv((1.0 - difference.intensity()) * 240.0, 1.0, 1.0)
+                } else {
+                    difference
+                };
+                difference.with_alpha(this.alpha())
+            })
+        })
+    }
+
     fn non_max_suppress(self) -> Self {
-        self.commit(|mut image| image.similar(|x, y| {
+        self.expects("Non-maximum suppression", &[Content::GradientMagnitude])
+            .commit(|mut image| image.similar(|x, y| {
             let suppress = |slice: [(usize, usize); 3]| -> bool {
                 let values: [Rgba ;3] = slice.into_iter()
                     .map(|x| image[x])
@@ -389,9 +1654,142 @@ impl Pipeline for CpuPipeline {
                 Rgba::BLACK
             }
         }))
+        .produces(Content::GradientMagnitude)
+    }
+
+    fn auto_white_balance(self) -> Self {
+        self.white_balance(|values| values.iter().sum::<f64>() / values.len().max(1) as f64)
+    }
+
+    fn auto_white_balance_percentile(self, percentile: f64) -> Self {
+        let fraction = (percentile / 100.0).clamp(0.0, 1.0);
+        self.white_balance(move |values| Histogram::from_values(256, values.iter().copied())
+            .percentile(fraction))
+    }
+
+    fn auto_levels(self, clip_percent: f64) -> Self {
+        let clip = (clip_percent / 100.0).clamp(0.0, 0.5);
+        self.commit(move |image| {
+            let ranges = (0..3)
+                .map(|c| {
+                    let histogram = Histogram::from_values(256, image.0.iter()
+                        .flatten()
+                        .filter_map(|pixel| pixel.into_iter().nth(c)));
+                    (histogram.percentile(clip), histogram.percentile(1.0 - clip))
+                })
+                .collect::<Vec<_>>();
+            image.similar(|x, y| image[(x, y)]
+                .into_iter()
+                .enumerate()
+                .map(|(c, v)| match ranges.get(c) {
+                    Some(&(low, high)) if high > low => ((v - low) / (high - low)).clamp(0.0, 1.0),
+                    _ => v,
+                })
+                .collect())
+        })
+    }
+
+    fn equalize_hist(self) -> Self {
+        self.commit(|image| {
+            let luminance = |pixel: &Rgba| (*pixel * Rgba::GRAYSCALE_FACTOR)
+                .into_iter()
+                .take(3)
+                .sum::<f64>();
+            let histogram = Histogram::from_values(256, image.0.iter()
+                .flatten()
+                .map(luminance));
+            let cdf = histogram.cdf();
+            // The darkest luminance present maps to black
+            let lowest = cdf.iter()
+                .copied()
+                .find(|f| *f > 0.0)
+                .unwrap_or(0.0);
+            image.similar(|x, y| {
+                let pixel = image[(x, y)];
+                let before = luminance(&pixel);
+                let after = match lowest < 1.0 {
+                    true => ((cdf[histogram.bin(before)] - lowest) / (1.0 - lowest)).clamp(0.0, 1.0),
+                    false => before,
+                };
+                if before > 0.0 {
+                    pixel.map_color(|c| (c * after / before).clamp(0.0, 1.0))
+                } else {
+                    Rgba::gray(after).with_alpha(pixel.alpha())
+                }
+            })
+        })
+    }
+
+    fn match_histogram(self, reference: &Image) -> Self {
+        let channel = |image: &Image, c: usize| Histogram::from_values(256, image.0.iter()
+            .flatten()
+            .filter_map(|pixel| pixel.into_iter().nth(c)));
+        let targets = (0..3)
+            .map(|c| channel(reference, c))
+            .collect::<Vec<_>>();
+        self.commit(move |image| {
+            let cdfs = (0..3)
+                .map(|c| channel(&image, c).cdf())
+                .collect::<Vec<_>>();
+            image.similar(|x, y| image[(x, y)]
+                .into_iter()
+                .enumerate()
+                .map(|(c, v)| match (cdfs.get(c), targets.get(c)) {
+                    (Some(cdf), Some(target)) => target.percentile(cdf[target.bin(v)]),
+                    _ => v,
+                })
+                .collect())
+        })
+    }
+
+    fn posterize(self, levels_per_channel: usize) -> Self {
+        assert!(levels_per_channel >= 2, "Posterize needs at least two levels, got {levels_per_channel}");
+        let steps = (levels_per_channel - 1) as f64;
+        self.commit(move |image| image.similar(|x, y| image[(x, y)]
+            .map_color(|v| (v.clamp(0.0, 1.0) * steps).round() / steps)))
+    }
+
+    fn brightness_contrast(self, brightness: f64, contrast: f64) -> Self {
+        self.commit(move |image| image.similar(|x, y| image[(x, y)]
+            .map_color(|v| ((v - 0.5) * contrast + 0.5 + brightness).clamp(0.0, 1.0))))
+    }
+
+    fn gamma(self, g: f64) -> Self {
+        self.check(move |_| (g <= 0.0)
+                .then(|| (Severity::Error, format!("Gamma must be positive, got {g}"))))
+            .commit(move |image| image.similar(|x, y| image[(x, y)]
+            .map_color(|v| v.max(0.0).powf(1.0 / g))))
+    }
+
+    fn levels(self, in_black: f64, in_white: f64, out_black: f64, out_white: f64) -> Self {
+        self.check(move |_| (in_white <= in_black)
+                .then(|| (Severity::Error, format!("Input white point {in_white} is not above the black point {in_black}"))))
+            .commit(move |image| image.similar(|x, y| image[(x, y)]
+            .map_color(|v| ((v - in_black) / (in_white - in_black)).clamp(0.0, 1.0) * (out_white - out_black) + out_black)))
+    }
+
+    fn normalize(self) -> Self {
+        self.commit(|image| {
+            let stats = image.stats();
+            let low = stats.min.red().min(stats.min.green()).min(stats.min.blue());
+            let high = stats.max.red().max(stats.max.green()).max(stats.max.blue());
+            if high <= low {
+                return image;
+            }
+            image.similar(|x, y| image[(x, y)]
+                .map_color(|v| (v - low) / (high - low)))
+        })
+    }
+
+    fn solarize(self, threshold: f64) -> Self {
+        self.commit(move |image| image.similar(|x, y| image[(x, y)]
+            .map_color(|v| if v >= threshold { 1.0 - v } else { v })))
     }
 
     fn quantize(self, thresholds: Vec<f64>) -> Self {
+        let empty = thresholds.is_empty();
+        let this = self.check(move |_| empty
+            .then(|| (Severity::Error, "Quantize has no thresholds".to_string())));
         let len = thresholds.len();
         let steps = thresholds.into_iter()
             .rev()
@@ -399,7 +1797,7 @@ impl Pipeline for CpuPipeline {
             .enumerate()
             .map(move |(n, threshold)| (Rgba::gray(n as f64 / len as f64), threshold))
             .collect::<Vec<_>>();
-        self.commit(move |image| image.similar(|x, y| {
+        this.commit(move |image| image.similar(|x, y| {
             let pixel: f64 = Into::<[f64; 4]>::into(image[(x, y)])
                 .into_iter()
                 .rev()
@@ -414,9 +1812,1086 @@ impl Pipeline for CpuPipeline {
         }))
     }
 
+    fn quantize_colors(self, colors: usize) -> Self {
+        self.check(move |_| (colors == 0)
+                .then(|| (Severity::Error, "Colour quantization needs at least one colour".to_string())))
+            .commit(move |image| {
+                let palette = median_cut(&image, colors);
+                image.similar(|x, y| nearest(&palette, image[(x, y)]))
+            })
+    }
+
+    fn convolve(self, kernel: &Image, anchor: Anchor, border: BorderMode, reduce: ReduceOp) -> Self {
+        let kernel = kernel.clone();
+        self.commit(move |image| image.convolve(&kernel, anchor, border, reduce))
+    }
+
+    fn gradient_with(self, x: Filter<Self>, y: Filter<Self>, norm: GradientNorm, normalize: bool) -> Self {
+        let kernel = |filter: Filter<Self>| match filter {
+            Filter::Convoluted(n) => Some(n.generate(0, 0)),
+            Filter::Custom(rows) => Some(Image::from_weights(&rows)),
+            Filter::Median(_) | Filter::Midpoint(_) => None,
+        };
+        let (kx, ky) = match (kernel(x), kernel(y)) {
+            (Some(kx), Some(ky)) => (kx, ky),
+            _ => return self.check(|_| Some((Severity::Error,
+                                             "Gradient kernels must be convolutions".to_string()))),
+        };
+        // Inputs in [0, 1] respond at most with the sum of the positive weights
+        let reach = |kernel: &Image| kernel.0.iter()
+            .flatten()
+            .map(|w| w.red().max(0.0))
+            .sum::<f64>()
+            .max(f64::EPSILON);
+        let max = match (&norm, normalize) {
+            (_, false) => 1.0,
+            (GradientNorm::L1, true) => reach(&kx) + reach(&ky),
+            (GradientNorm::L2, true) => reach(&kx).hypot(reach(&ky)),
+        };
+        self.commit(move |image| {
+            let dx = image.convolve(&kx, Anchor::Center, BorderMode::Replicate, ReduceOp::Sum);
+            let dy = image.convolve(&ky, Anchor::Center, BorderMode::Replicate, ReduceOp::Sum);
+            image.similar(|x, y| {
+                let dx: [f64; 4] = dx[(x, y)].into();
+                let dy: [f64; 4] = dy[(x, y)].into();
+                let magnitude = |c: usize| match norm {
+                    GradientNorm::L1 => dx[c].abs() + dy[c].abs(),
+                    GradientNorm::L2 => dx[c].hypot(dy[c]),
+                } / max;
+                (magnitude(0), magnitude(1), magnitude(2), image[(x, y)].alpha()).into()
+            })
+        })
+        .produces(Content::GradientMagnitude)
+    }
+
+    fn gradient_direction(self) -> Self {
+        self.commit(|image| image.similar(|x, y| {
+            let luminance = |x: usize, y: usize| image[(x, y)].intensity();
+            let xp = x.saturating_sub(1);
+            let xn = (x + 1).min(image.width() - 1);
+            let yp = y.saturating_sub(1);
+            let yn = (y + 1).min(image.height() - 1);
+            let dx = luminance(xn, y) - luminance(xp, y);
+            let dy = luminance(x, yn) - luminance(x, yp);
+            Rgba::gray((dy.atan2(dx) + PI) / (2.0 * PI))
+                .with_alpha(image[(x, y)].alpha())
+        }))
+        .produces(Content::Grayscale)
+    }
+
+    fn hysteresis(self, low: f64, high: f64) -> Self {
+        self.expects("Hysteresis", &[Content::GradientMagnitude, Content::Grayscale])
+            .check(move |_| (low > high)
+                .then(|| (Severity::Error, format!("Low threshold {low} is above the high threshold {high}"))))
+            .check(move |_| (!(0.0..=1.0).contains(&low) || !(0.0..=1.0).contains(&high))
+                .then(|| (Severity::Warning, format!("Thresholds {low} and {high} are outside of [0, 1]"))))
+            .commit(move |image| {
+            let (width, height) = (image.width(), image.height());
+            let strength = |x: usize, y: usize| image[(x, y)].intensity();
+            let mut edges = vec![vec![false; height]; width];
+            let mut queue = (0..width)
+                .flat_map(|x| (0..height)
+                    .map(move |y| (x, y)))
+                .filter(|&(x, y)| strength(x, y) >= high)
+                .collect::<VecDeque<_>>();
+            queue.iter()
+                .for_each(|&(x, y)| edges[x][y] = true);
+
+            while let Some((x, y)) = queue.pop_front() {
+                let neighbours = (x.saturating_sub(1)..=(x + 1).min(width - 1))
+                    .flat_map(|nx| (y.saturating_sub(1)..=(y + 1).min(height - 1))
+                        .map(move |ny| (nx, ny)));
+                for (nx, ny) in neighbours {
+                    if !edges[nx][ny] && strength(nx, ny) >= low {
+                        edges[nx][ny] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+
+            image.similar(|x, y| if edges[x][y] {
+                Rgba::WHITE
+            } else {
+                Rgba::BLACK
+            })
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    fn hysteresis_otsu(self) -> Self {
+        self.expects("Hysteresis", &[Content::GradientMagnitude, Content::Grayscale])
+            .commit(|image| {
+            let high = Histogram::from_values(256, image.0.iter()
+                    .flatten()
+                    .map(Rgba::intensity)
+                    .filter(|v| *v > 0.0))
+                .otsu();
+            CpuPipeline::default()
+                .hysteresis(high / 2.0, high)
+                .apply(&image)
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    fn hysteresis_median(self, sigma: f64) -> Self {
+        self.expects("Hysteresis", &[Content::GradientMagnitude, Content::Grayscale])
+            .commit(move |image| {
+            let median = Histogram::from_values(256, image.0.iter()
+                    .flatten()
+                    .map(Rgba::intensity)
+                    .filter(|v| *v > 0.0))
+                .median();
+            CpuPipeline::default()
+                .hysteresis(((1.0 - sigma) * median).max(0.0), ((1.0 + sigma) * median).min(1.0))
+                .apply(&image)
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    fn dither(self, levels: usize, method: DitherMethod) -> Self {
+        assert!(levels >= 2, "Dithering needs at least 2 levels, got {levels}");
+        let steps = (levels - 1) as f64;
+        let reduce = move |value: f64| (value.clamp(0.0, 1.0) * steps).round() / steps;
+        match method {
+            DitherMethod::FloydSteinberg => self.commit(move |image| image.diffuse_error(|old| old.map_color(reduce))),
+            DitherMethod::Ordered(size) => {
+                let side = size.side();
+                let matrix = size.matrix();
+                self.commit(move |image| image.similar(|x, y| {
+                    let offset = (matrix[x % side][y % side] as f64 + 0.5)
+                        / (side * side) as f64 - 0.5;
+                    image[(x, y)].map_color(|value| reduce(value + offset / steps))
+                }))
+            }
+        }
+    }
+
+    fn dither_palette(self, palette: Vec<Rgba>) -> Self {
+        let empty = palette.is_empty();
+        self.check(move |_| empty
+                .then(|| (Severity::Error, "Dithering needs a non-empty palette".to_string())))
+            .commit(move |image| image.diffuse_error(|old| nearest(&palette, old)))
+    }
+
+    fn correct_chromatic_aberration(self, red: ChannelCorrection, blue: ChannelCorrection) -> Self {
+        self.commit(move |image| {
+            let cx = (image.width() as f64 - 1.0) / 2.0;
+            let cy = (image.height() as f64 - 1.0) / 2.0;
+            let norm = (cx * cx + cy * cy).sqrt().max(1.0);
+            let source = |c: &ChannelCorrection, x: usize, y: usize| {
+                let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+                let r2 = (dx * dx + dy * dy) / (norm * norm);
+                let factor = c.scale + c.k1 * r2 + c.k2 * r2 * r2;
+                (cx + dx * factor + c.shift.0, cy + dy * factor + c.shift.1)
+            };
+            image.similar(|x, y| {
+                let pixel = image[(x, y)];
+                let (rx, ry) = source(&red, x, y);
+                let (bx, by) = source(&blue, x, y);
+                (
+                    image.bilinear(rx, ry).red(),
+                    pixel.green(),
+                    image.bilinear(bx, by).blue(),
+                    pixel.alpha()
+                ).into()
+            })
+        })
+    }
+
+    fn despeckle(self, threshold: f64) -> Self {
+        self.commit(move |image| image.similar(|x, y| {
+            let pixel = image[(x, y)];
+            let window = image.neighbourhood(x, y, 1);
+            let isolated = window.iter()
+                .enumerate()
+                .filter(|(n, _)| *n != window.len() / 2)
+                .all(|(_, neighbour)| (pixel - *neighbour)
+                    .into_iter()
+                    .take(3)
+                    .any(|d| d.abs() > threshold));
+            if isolated {
+                Rgba::median(&window).with_alpha(pixel.alpha())
+            } else {
+                pixel
+            }
+        }))
+    }
+
+    fn box_filter_integral(self, size: usize) -> Self {
+        let weights = self.weights.clone();
+        self.check(fits_in_image("Box filter", size, size))
+            .commit(move |image| {
+            let Some(weights) = &weights else {
+                let integral = IntegralImage::new(&image);
+                return image.similar(|x, y| integral.mean(x, y, size / 2));
+            };
+            // The window sums of the weighted pixels over those of the weights
+            let weighted = IntegralImage::new(&image.similar(|x, y| image[(x, y)] * weights.weight(x, y)));
+            let total = IntegralImage::new(&image.similar(|x, y| Rgba::gray(weights.weight(x, y))));
+            image.similar(|x, y| {
+                let total = total.mean(x, y, size / 2).red();
+                if total > f64::EPSILON {
+                    weighted.mean(x, y, size / 2) / total
+                } else {
+                    image[(x, y)]
+                }
+            })
+        })
+    }
+
+    fn adaptive_mean_threshold(self, size: usize, offset: f64) -> Self {
+        self.check(fits_in_image("Threshold window", size, size))
+            .commit(move |image| {
+            let integral = IntegralImage::new(&image);
+            image.similar(|x, y| {
+                if image[(x, y)].intensity() > integral.mean(x, y, size / 2).intensity() - offset {
+                    Rgba::WHITE
+                } else {
+                    Rgba::BLACK
+                }
+            })
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    fn zero_crossings(self, threshold: f64) -> Self {
+        self.commit(move |image| {
+            let mut edges = vec![vec![false; image.height()]; image.width()];
+            for x in 0..image.width() {
+                for y in 0..image.height() {
+                    let here = image[(x, y)].intensity();
+                    for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                        if nx >= image.width() || ny >= image.height() {
+                            continue;
+                        }
+                        let there = image[(nx, ny)].intensity();
+                        if here.signum() != there.signum() && (here - there).abs() > threshold {
+                            if here.abs() <= there.abs() {
+                                edges[x][y] = true;
+                            } else {
+                                edges[nx][ny] = true;
+                            }
+                        }
+                    }
+                }
+            }
+            image.similar(|x, y| if edges[x][y] { Rgba::WHITE } else { Rgba::BLACK })
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    fn threshold_otsu(self) -> Self {
+        self.commit(|image| {
+            let threshold = Histogram::from_values(256, image.0.iter()
+                    .flatten()
+                    .map(Rgba::intensity))
+                .otsu();
+            image.similar(|x, y| if image[(x, y)].intensity() >= threshold {
+                Rgba::WHITE
+            } else {
+                Rgba::BLACK
+            })
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    fn niblack_threshold(self, size: usize, k: f64) -> Self {
+        self.local_threshold(size, move |mean, deviation| mean + k * deviation)
+    }
+
+    fn sauvola_threshold(self, size: usize, k: f64) -> Self {
+        // Largest standard deviation of values in [0, 1]
+        const RANGE: f64 = 0.5;
+        self.local_threshold(size, move |mean, deviation| mean * (1.0 + k * (deviation / RANGE - 1.0)))
+    }
+
+    fn simulate_cvd(self, deficiency: ColorVisionDeficiency) -> Self {
+        let matrix = deficiency.matrix();
+        self.commit(move |image| image.similar(|x, y| image[(x, y)]
+            .linearize()
+            .transform(matrix)
+            .map_color(|c| c.clamp(0.0, 1.0))
+            .delinearize()))
+    }
+
     fn gaussian_blur(self, size: usize, variance: f64) -> Self {
-        self.filter(CpuGenerator::new(5)
-            .gaussian_needle(0.6))
+        let this = self.check(move |_| (size % 2 != 1)
+                .then(|| (Severity::Error, format!("Gaussian blur size must be odd, got {size}"))))
+            .check(move |_| (variance <= 0.0)
+                .then(|| (Severity::Error, format!("Gaussian blur variance must be positive, got {variance}"))));
+        // The kernel is built right away, and cannot be for such a variance
+        if variance <= 0.0 {
+            return this;
+        }
+        this.filter(CpuGenerator::new(size)
+            .gaussian_needle(variance.sqrt()))
+    }
+
+    fn freq_lowpass(self, response: FrequencyResponse, cutoff: f64) -> Self {
+        self.check(positive_cutoff(cutoff))
+            .frequency_filter(move |fu, fv| response.lowpass(fu.hypot(fv), cutoff))
+    }
+
+    fn freq_highpass(self, response: FrequencyResponse, cutoff: f64) -> Self {
+        self.check(positive_cutoff(cutoff))
+            .frequency_filter(move |fu, fv| 1.0 - response.lowpass(fu.hypot(fv), cutoff))
     }
 
-}
\ No newline at end of file
+    fn notch_reject(self, notches: Vec<(f64, f64)>, radius: f64) -> Self {
+        // Gaussian notches, each mirrored through the origin since the
+        // spectrum of a real image is symmetric
+        self.check(positive_cutoff(radius))
+            .frequency_filter(move |fu, fv| notches.iter()
+                .flat_map(|&(nu, nv)| [(nu, nv), (-nu, -nv)])
+                .map(|(nu, nv)| {
+                    let distance = (fu - nu).hypot(fv - nv);
+                    1.0 - (-distance * distance / (2.0 * radius * radius)).exp()
+                })
+                .product())
+    }
+
+    fn wavelet_denoise(self, levels: usize, threshold: f64, shrinkage: Shrinkage) -> Self {
+        let block = 1 << levels;
+        self.check(fits_in_image("Wavelet block", block, block))
+            .commit(move |image| {
+                // Replicated up to a multiple of the coarsest block, cropped after
+                let (width, height) = (image.width().div_ceil(block) * block, image.height().div_ceil(block) * block);
+                let channels = (0..3)
+                    .map(|c| {
+                        let mut data = (0..width)
+                            .map(|x| (0..height)
+                                .map(|y| {
+                                    let pixel: [f64; 4] = image.get(x as i64, y as i64, BorderMode::Replicate).into();
+                                    pixel[c]
+                                })
+                                .collect::<Vec<_>>())
+                            .collect::<Vec<_>>();
+                        haar2d(&mut data, levels);
+                        shrink(&mut data, levels, threshold, shrinkage);
+                        inverse_haar2d(&mut data, levels);
+                        data
+                    })
+                    .collect::<Vec<_>>();
+                image.similar(|x, y| Rgba::from((channels[0][x][y], channels[1][x][y], channels[2][x][y],
+                                                 image[(x, y)].alpha())))
+            })
+    }
+
+    fn erode(self, element: StructuringElement) -> Self {
+        let (width, height) = element.size();
+        self.check(fits_in_image("Structuring element", width, height))
+            .commit(move |image| image.morphology(&element.offsets(), Rgba::min))
+    }
+
+    fn dilate(self, element: StructuringElement) -> Self {
+        let (width, height) = element.size();
+        // Reflected, like `Mask::dilate`, so that uneven elements grow the
+        // regions back where erosion shrank them
+        let offsets = element.offsets()
+            .into_iter()
+            .map(|(i, j)| (-i, -j))
+            .collect::<Vec<_>>();
+        self.check(fits_in_image("Structuring element", width, height))
+            .commit(move |image| image.morphology(&offsets, Rgba::max))
+    }
+
+    fn tophat(self, element: StructuringElement) -> Self {
+        self.sub(CpuPipeline::default().open(element))
+    }
+
+    fn blackhat(self, element: StructuringElement) -> Self {
+        self.commit(move |image| {
+            let closed = CpuPipeline::default()
+                .close(element)
+                .apply(&image);
+            image.similar(|x, y| (closed[(x, y)] - image[(x, y)])
+                .with_alpha(image[(x, y)].alpha()))
+        })
+    }
+
+    fn skeletonize(self) -> Self {
+        self.expects("Skeletonization", &[Content::BinaryMask])
+            .commit(|image| {
+            let (width, height) = (image.width() as i64, image.height() as i64);
+            let mut on = (0..width)
+                .map(|x| (0..height)
+                    .map(|y| image[(x as usize, y as usize)].intensity() > 0.5)
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            // Clockwise from north: P2 to P9
+            const AROUND: [(i64, i64); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+            loop {
+                let mut changed = false;
+                for pass in 0..2 {
+                    let removed = (0..width)
+                        .flat_map(|x| (0..height)
+                            .map(move |y| (x, y)))
+                        .filter(|&(x, y)| on[x as usize][y as usize])
+                        .filter(|&(x, y)| {
+                            let p = AROUND.map(|(i, j)| {
+                                let (nx, ny) = (x + i, y + j);
+                                nx >= 0 && ny >= 0 && nx < width && ny < height && on[nx as usize][ny as usize]
+                            });
+                            let neighbours = p.iter().filter(|v| **v).count();
+                            let transitions = (0..8)
+                                .filter(|n| !p[*n] && p[(n + 1) % 8])
+                                .count();
+                            let (p2, p4, p6, p8) = (p[0], p[2], p[4], p[6]);
+                            let directional = if pass == 0 {
+                                !(p4 && p6 && (p2 || p8))
+                            } else {
+                                !(p2 && p8 && (p4 || p6))
+                            };
+                            (2..=6).contains(&neighbours) && transitions == 1 && directional
+                        })
+                        .collect::<Vec<_>>();
+                    changed |= !removed.is_empty();
+                    for (x, y) in removed {
+                        on[x as usize][y as usize] = false;
+                    }
+                }
+                if !changed {
+                    break;
+                }
+            }
+            image.similar(|x, y| if on[x][y] { Rgba::WHITE } else { Rgba::BLACK })
+        })
+        .produces(Content::BinaryMask)
+    }
+
+    fn frangi(self, scales: Vec<f64>) -> Self {
+        let valid = !scales.is_empty() && scales.iter().all(|s| *s > 0.0);
+        let this = self.check(move |_| (!valid)
+                .then(|| (Severity::Error, "Frangi needs at least one scale, and only positive ones".to_string())));
+        // Such scales give no usable derivative kernels
+        if !valid {
+            return this;
+        }
+        // Sensitivity to blobs against lines
+        const BETA: f64 = 0.5;
+        this.commit(move |image| {
+            let mut vesselness = vec![vec![0.0f64; image.height()]; image.width()];
+            for sigma in scales {
+                let size = 2 * (3.0 * sigma).ceil() as usize + 1;
+                // Scaled by sigma squared so that responses at different scales compare
+                let hessian = [(2, 0), (1, 1), (0, 2)].map(|(x_order, y_order)| {
+                    let kernel = Image::from_weights(&gaussian_derivative_weights(size, sigma, x_order, y_order));
+                    let response = image.convolve(&kernel, Anchor::Center, BorderMode::Replicate, ReduceOp::Sum);
+                    move |x: usize, y: usize| response[(x, y)].intensity() * sigma * sigma
+                });
+                let eigenvalues = (0..image.width())
+                    .map(|x| (0..image.height())
+                        .map(|y| {
+                            let (xx, xy, yy) = (hessian[0](x, y), hessian[1](x, y), hessian[2](x, y));
+                            let spread = (((xx - yy) / 2.0).powi(2) + xy * xy).sqrt();
+                            let (a, b) = ((xx + yy) / 2.0 + spread, (xx + yy) / 2.0 - spread);
+                            // Smallest magnitude first
+                            if a.abs() <= b.abs() { (a, b) } else { (b, a) }
+                        })
+                        .collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                // Structure is judged against half of the strongest at this scale
+                let c = eigenvalues.iter()
+                    .flatten()
+                    .map(|(a, b)| a.hypot(*b))
+                    .fold(0.0, f64::max) / 2.0;
+                if c == 0.0 {
+                    continue;
+                }
+                for (x, column) in eigenvalues.iter().enumerate() {
+                    for (y, &(small, large)) in column.iter().enumerate() {
+                        // Bright ridges curve down across their direction
+                        if large >= 0.0 {
+                            continue;
+                        }
+                        let blobness = small / large;
+                        let structure = small.hypot(large);
+                        let v = (-blobness * blobness / (2.0 * BETA * BETA)).exp()
+                            * (1.0 - (-structure * structure / (2.0 * c * c)).exp());
+                        vesselness[x][y] = vesselness[x][y].max(v);
+                    }
+                }
+            }
+            let max = vesselness.iter()
+                .flatten()
+                .fold(0.0, |a: f64, b| a.max(*b));
+            image.similar(|x, y| Rgba::gray(if max > 0.0 { vesselness[x][y] / max } else { 0.0 }))
+        })
+        .produces(Content::Grayscale)
+    }
+
+    fn orient(self, orientation: Orientation) -> Self {
+        self.commit(move |image| image.oriented(orientation))
+            .reshape(move |info| match orientation {
+                Orientation::Transpose | Orientation::Rotate90 | Orientation::Transverse | Orientation::Rotate270 =>
+                    ImageInfo { width: info.height, height: info.width, ..info },
+                _ => info,
+            })
+    }
+
+    fn pyr_down(self) -> Self {
+        self.commit(|image| image.pyr_down())
+            .reshape(|info| ImageInfo { width: info.width.div_ceil(2), height: info.height.div_ceil(2), ..info })
+    }
+
+    fn decimate(self, factor: usize) -> Self {
+        let this = self.check(move |_| (factor == 0)
+                .then(|| (Severity::Error, "Decimation factor must be positive".to_string())));
+        // Neither the image nor its size can be divided by zero
+        if factor == 0 {
+            return this;
+        }
+        this.commit(move |image| image.decimate(factor))
+            .reshape(move |info| ImageInfo { width: info.width.div_ceil(factor), height: info.height.div_ceil(factor), ..info })
+    }
+
+    fn resize(self, width: usize, height: usize, interpolation: Interpolation) -> Self {
+        self.check(move |_| (width == 0 || height == 0)
+                .then(|| (Severity::Error, format!("Cannot resize to {width}x{height}"))))
+            .commit(move |image| image.resize(width, height, interpolation))
+            .reshape(move |info| ImageInfo { width, height, ..info })
+    }
+
+    fn add_border(self, size: usize, color: Rgba) -> Self {
+        self.commit(move |image| image.padded(size, size, size, size, color))
+            .reshape(move |info| ImageInfo { width: info.width + 2 * size, height: info.height + 2 * size, ..info })
+    }
+
+    fn pad_to_aspect(self, ratio: f64, color: Rgba) -> Self {
+        let this = self.check(move |_| (ratio <= 0.0)
+                .then(|| (Severity::Error, format!("Aspect ratio must be positive, got {ratio}"))));
+        if ratio <= 0.0 {
+            return this;
+        }
+        let padded = move |width: usize, height: usize| {
+            if (width as f64) < height as f64 * ratio {
+                ((height as f64 * ratio).round() as usize, height)
+            } else {
+                (width, (width as f64 / ratio).round() as usize)
+            }
+        };
+        this.commit(move |image| {
+            let (width, height) = padded(image.width(), image.height());
+            let (left, top) = ((width - image.width()) / 2, (height - image.height()) / 2);
+            image.padded(left, top, width - image.width() - left, height - image.height() - top, color)
+        })
+            .reshape(move |info| {
+                let (width, height) = padded(info.width, info.height);
+                ImageInfo { width, height, ..info }
+            })
+    }
+
+    fn unsharp_mask(self, radius: f64, amount: f64, threshold: f64) -> Self {
+        assert!(radius > 0.0, "Unsharp mask radius must be positive, got {radius}");
+        let kernel = gaussian_kernel(radius);
+        self.commit(move |image| {
+            let blurred = image.separable(&kernel);
+            image.similar(|x, y| {
+                let pixel = image[(x, y)];
+                let detail = pixel - blurred[(x, y)];
+                if detail.into_iter().take(3).all(|d| d.abs() <= threshold) {
+                    return pixel;
+                }
+                (pixel + detail * amount).with_alpha(pixel.alpha())
+            })
+        })
+    }
+
+    fn bilateral(self, spatial_sigma: f64, range_sigma: f64) -> Self {
+        assert!(spatial_sigma > 0.0 && range_sigma > 0.0, "Bilateral sigmas must be positive");
+        let radius = (2.0 * spatial_sigma).ceil() as usize;
+        self.check(fits_in_image("Bilateral window", 2 * radius + 1, 2 * radius + 1))
+            .commit(move |image| image.similar(|x, y| {
+            let centre = image[(x, y)];
+            let window = image.neighbourhood(x, y, radius);
+            let side = 2 * radius + 1;
+            let (sum, total) = window.iter()
+                .enumerate()
+                .map(|(n, pixel)| {
+                    let (dx, dy) = ((n / side) as f64 - radius as f64, (n % side) as f64 - radius as f64);
+                    let distance = (dx * dx + dy * dy) / (2.0 * spatial_sigma * spatial_sigma);
+                    let difference = (centre - *pixel)
+                        .into_iter()
+                        .take(3)
+                        .map(|d| d * d)
+                        .sum::<f64>() / (2.0 * range_sigma * range_sigma);
+                    (*pixel, (-distance - difference).exp())
+                })
+                .fold((Rgba::gray(0.0).with_alpha(0.0), 0.0), |(sum, total), (pixel, weight)|
+                    (sum + pixel * weight, total + weight));
+            sum / total
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BayerSize, BorderMode, ColorVisionDeficiency, DitherMethod, Filter, FrequencyResponse, GradientNorm, Interpolation, OutputSize, Stacking, StructuringElement};
+    use crate::pipeline::{Generator, Image as _, ImageInfo, Pipeline, Severity};
+    use std::f64::consts::PI;
+    use crate::rgba::Rgba;
+    use crate::mask::Mask;
+    use crate::metrics::mse;
+    use super::{average_aligned, fuse_exposures, make_contact_sheet, phase_correlation, CpuGenerator, CpuPipeline, Image};
+
+    #[test]
+    fn identity_kernel_keeps_image_in_place() {
+        let image = Image::from_fn(5, 4, |x, y| Rgba::gray((x * 4 + y) as f64 / 20.0));
+        let kernel = Image::from_fn(3, 3, |x, y| Rgba::gray(if (x, y) == (1, 1) { 1.0 } else { 0.0 }));
+        let out = CpuPipeline::default()
+            .filter(Filter::Convoluted(CpuPipeline::default().commit(move |_| kernel.clone())))
+            .apply(&image);
+        for x in 0..image.width() {
+            for y in 0..image.height() {
+                assert_eq!(out[(x, y)].red(), image[(x, y)].red());
+            }
+        }
+    }
+
+    #[test]
+    fn custom_kernel_convolves_and_rejects_ragged_rows() {
+        let image = Image::from_fn(5, 1, |x, _| Rgba::gray(x as f64 / 5.0));
+        let out = CpuPipeline::default()
+            .filter(Filter::Custom(vec![vec![0.0, 0.0, 1.0]]))
+            .apply(&image);
+        assert_eq!(out[(1, 0)].red(), image[(2, 0)].red());
+        let issues = CpuPipeline::default()
+            .filter(Filter::Custom(vec![vec![1.0, 1.0], vec![1.0]]))
+            .validate(&ImageInfo::new(8, 8));
+        assert_eq!(issues[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn border_modes_sample_outside_the_image() {
+        let image = Image::from_fn(4, 1, |x, _| Rgba::gray(x as f64 / 4.0));
+        let shifted = |mode| CpuPipeline::default()
+            .border(mode)
+            .offset(-2, 0)
+            .apply(&image);
+        let reds = |image: Image| (0..4).map(|x| image[(x, 0)].red() * 4.0).collect::<Vec<_>>();
+        assert_eq!(reds(shifted(BorderMode::Replicate)), vec![0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(reds(shifted(BorderMode::Reflect)), vec![2.0, 1.0, 0.0, 1.0]);
+        assert_eq!(reds(shifted(BorderMode::Wrap)), vec![2.0, 3.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn output_sizes_follow_the_window() {
+        let image = Image::from_fn(6, 5, |x, y| Rgba::gray((x + y) as f64 / 10.0));
+        let sized = |mode| CpuPipeline::default()
+            .output_size(mode)
+            .filter(Filter::Custom(vec![vec![0.0, 1.0, 0.0]; 3]));
+        let valid = sized(OutputSize::Valid).apply(&image);
+        assert_eq!((valid.width(), valid.height()), (4, 3));
+        assert!((valid[(0, 0)].red() - image[(1, 1)].red() * 3.0).abs() < 1e-9);
+        let full = sized(OutputSize::Full).apply(&image);
+        assert_eq!((full.width(), full.height()), (8, 7));
+        assert!((full[(2, 2)].red() - image[(1, 1)].red() * 3.0).abs() < 1e-9);
+        let info = sized(OutputSize::Full).validate(&ImageInfo::new(6, 5));
+        assert!(info.is_empty());
+        // The kernel fits once the image is padded, and the output is 4x4
+        assert!(sized(OutputSize::Full).validate(&ImageInfo::new(2, 2)).is_empty());
+        assert_eq!(sized(OutputSize::Same).validate(&ImageInfo::new(2, 2)).len(), 1);
+        let then = |size: usize| sized(OutputSize::Full)
+            .output_size(OutputSize::Same)
+            .filter(Filter::Custom(vec![vec![1.0; size]; size]))
+            .validate(&ImageInfo::new(2, 2));
+        assert!(then(4).is_empty());
+        assert_eq!(then(5).len(), 1);
+    }
+
+    #[test]
+    fn lowpass_removes_checkerboard_and_highpass_keeps_it() {
+        let image = Image::from_fn(8, 8, |x, y| Rgba::gray(((x + y) % 2) as f64));
+        let low = CpuPipeline::default()
+            .freq_lowpass(FrequencyResponse::Butterworth(2), 0.2)
+            .apply(&image);
+        let high = CpuPipeline::default()
+            .freq_highpass(FrequencyResponse::Ideal, 0.2)
+            .apply(&image);
+        for (x, y) in [(0, 0), (3, 4), (7, 2)] {
+            assert!((low[(x, y)].red() - 0.5).abs() < 0.05);
+            assert!((high[(x, y)].red() - (image[(x, y)].red() - 0.5)).abs() < 1e-9);
+            assert_eq!(high[(x, y)].alpha(), 1.0);
+        }
+    }
+
+    #[test]
+    fn notch_removes_periodic_noise() {
+        let image = Image::from_fn(16, 16, |x, _| Rgba::gray(0.5 + 0.2 * (PI * x as f64 / 2.0).cos()));
+        let out = CpuPipeline::default()
+            .notch_reject(vec![(0.25, 0.0)], 0.05)
+            .apply(&image);
+        for x in 0..16 {
+            assert!((out[(x, 5)].red() - 0.5).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn normalize_stretches_to_the_unit_range() {
+        let image = Image::from_fn(4, 1, |x, _| Rgba::gray(0.2 + 0.1 * x as f64));
+        let stats = image.stats();
+        assert!((stats.min.red() - 0.2).abs() < 1e-12 && (stats.max.red() - 0.5).abs() < 1e-12);
+        assert!((stats.mean.green() - 0.35).abs() < 1e-12);
+        assert!((stats.std_dev.blue() - 0.0125f64.sqrt()).abs() < 1e-12);
+        let stats = CpuPipeline::default().normalize().apply(&image).stats();
+        assert!(stats.min.red().abs() < 1e-12 && (stats.max.red() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn diff_amplifies_and_colorizes() {
+        let image = Image::from_fn(2, 1, |x, _| Rgba::gray(0.5 + 0.01 * x as f64));
+        let dimmer = || CpuPipeline::default().levels(0.0, 1.0, 0.0, 0.99);
+        let out = CpuPipeline::default()
+            .diff(dimmer(), 10.0, false)
+            .apply(&image);
+        assert!((out[(0, 0)].red() - 0.05).abs() < 1e-9);
+        let out = CpuPipeline::default()
+            .diff(dimmer(), 1000.0, true)
+            .apply(&image);
+        assert_eq!((out[(1, 0)].red(), out[(1, 0)].blue()), (1.0, 0.0));
+    }
+
+    #[test]
+    fn resize_keeps_ramps_and_flat_areas() {
+        let ramp = Image::from_fn(8, 4, |x, _| Rgba::gray(x as f64 / 7.0));
+        let up = ramp.resize(16, 8, Interpolation::Bicubic);
+        assert_eq!((up.width(), up.height()), (16, 8));
+        // Away from the clamped edges a cubic reproduces a linear ramp exactly
+        let expected = ((7.0 + 0.5) * 0.5 - 0.5) / 7.0;
+        assert!((up[(7, 3)].red() - expected).abs() < 1e-12);
+        let flat = Image::from_fn(64, 48, |_, _| Rgba::gray(0.25));
+        let down = CpuPipeline::default()
+            .resize(5, 3, Interpolation::Bilinear)
+            .apply(&flat);
+        assert_eq!((down.width(), down.height()), (5, 3));
+        assert!((down[(4, 2)].red() - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn contact_sheet_lays_out_cells() {
+        let images = [Image::from_fn(8, 4, |_, _| Rgba::gray(1.0)), Image::empty(0, 0), Image::empty(3, 3)];
+        let labels = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let sheet = make_contact_sheet(&images, 2, 4, None);
+        assert_eq!((sheet.width(), sheet.height()), (2 * 8 + 4, 2 * 8 + 4));
+        // The 8x4 image shrinks to 4x2, centred vertically in its cell
+        assert_eq!(sheet[(4, 5)].red(), 1.0);
+        assert_eq!(sheet[(4, 4)].red(), 0.0);
+        let sheet = make_contact_sheet(&images, 2, 4, Some(&labels));
+        assert_eq!(sheet.height(), 2 * (8 + 12) + 4);
+        // At this size the captions hold a single character, centred under the thumbnail
+        let labels = ["abc".to_string(), "b".to_string(), "c".to_string()];
+        let sheet = make_contact_sheet(&images, 2, 8, Some(&labels));
+        let caption = |left: usize| (left..left + 8)
+            .flat_map(|x| (12..24)
+                .map(move |y| (x - left, y)))
+            .filter(|&(x, y)| sheet[(x + left, y)].red() == 1.0)
+            .collect::<Vec<_>>();
+        assert!(!caption(4).is_empty());
+        assert!(caption(4).iter().all(|&(x, y)| (1..7).contains(&x) && (13..23).contains(&y)));
+        assert_ne!(caption(4), caption(16));
+        let sheet = make_contact_sheet(&images, 2, 0, None);
+        assert_eq!((sheet.width(), sheet.height()), (12, 12));
+    }
+
+    #[test]
+    fn gaussian_blur_has_the_requested_variance() {
+        let impulse = Image::from_fn(15, 15, |x, y| Rgba::gray(if (x, y) == (7, 7) { 1.0 } else { 0.0 }));
+        let kernel = CpuPipeline::default()
+            .gaussian_blur(15, 2.0)
+            .apply(&impulse);
+        let (total, moment) = (0..15)
+            .flat_map(|x| (0..15)
+                .map(move |y| (x, y)))
+            .map(|(x, y)| (kernel[(x, y)].red(), kernel[(x, y)].red() * (x as f64 - 7.0).powi(2)))
+            .fold((0.0, 0.0), |(t, m), (w, wm)| (t + w, m + wm));
+        assert!((moment / total - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn blur_if_larger_than_spreads_by_sigma() {
+        let impulse = Image::from_fn(21, 21, |x, y| Rgba::gray(if (x, y) == (10, 10) { 1.0 } else { 0.0 }));
+        let kernel = CpuPipeline::default()
+            .blur_if_larger_than(0.0, 2.0)
+            .apply(&impulse);
+        let (total, moment) = (0..21)
+            .flat_map(|x| (0..21)
+                .map(move |y| (x, y)))
+            .map(|(x, y)| (kernel[(x, y)].red(), kernel[(x, y)].red() * (x as f64 - 10.0).powi(2)))
+            .fold((0.0, 0.0), |(t, m), (w, wm)| (t + w, m + wm));
+        // The kernel is cut off at three sigma, which trims a little of the variance
+        assert!((moment / total - 4.0).abs() < 0.15);
+        let untouched = CpuPipeline::default()
+            .blur_if_larger_than(1.0, 2.0)
+            .apply(&impulse);
+        assert_eq!(untouched[(10, 10)].red(), 1.0);
+    }
+
+    #[test]
+    fn remap_keeps_or_shifts_the_image() {
+        let image = Image::from_fn(4, 3, |x, y| Rgba::gray((x * 3 + y) as f64 / 12.0));
+        let map = |dx: f64, dy: f64| (
+            (0..4).map(|x| vec![x as f64 + dx; 3]).collect::<Vec<_>>(),
+            (0..4).map(|_| (0..3).map(|y| y as f64 + dy).collect()).collect::<Vec<_>>(),
+        );
+        let (map_x, map_y) = map(0.0, 0.0);
+        let same = image.remap(&map_x, &map_y, Interpolation::Bilinear);
+        assert!((0..4).all(|x| (0..3).all(|y| same[(x, y)].red() == image[(x, y)].red())));
+        let (map_x, map_y) = map(1.0, 0.5);
+        let shifted = image.remap(&map_x, &map_y, Interpolation::Bilinear);
+        assert!((shifted[(0, 0)].red() - (image[(1, 0)].red() + image[(1, 1)].red()) / 2.0).abs() < 1e-12);
+        // Sampling past the edge clamps to it
+        assert_eq!(shifted[(3, 2)].red(), image[(3, 2)].red());
+    }
+
+    #[test]
+    fn median_removes_salt_noise() {
+        let image = Image::from_fn(5, 5, |x, y| Rgba::gray(if (x, y) == (2, 2) { 1.0 } else { 0.2 }));
+        let out = CpuPipeline::default()
+            .filter(Filter::Median(3))
+            .apply(&image);
+        assert_eq!(out[(2, 2)].red(), 0.2);
+    }
+
+    #[test]
+    fn tiles_cover_the_image_with_halo() {
+        let image = Image::from_fn(5, 3, |x, y| Rgba::gray((x * 3 + y) as f64 / 15.0));
+        let tiles = image.tiles(2, 2, 1).collect::<Vec<_>>();
+        assert_eq!(tiles.len(), 6);
+        let last = &tiles[5];
+        assert_eq!((last.x, last.y, last.width, last.height), (4, 2, 1, 1));
+        assert_eq!(last.image[(1, 1)].red(), image[(4, 2)].red());
+        assert_eq!(last.image[(2, 2)].red(), image[(4, 2)].red());
+        assert_eq!(tiles[0].image[(2, 2)].red(), image[(1, 1)].red());
+
+        let mut copy = image.clone();
+        assert_eq!(copy.tiles_mut(2, 2).count(), tiles.len());
+        for (tile, tile_mut) in tiles.iter().zip(copy.tiles_mut(2, 2)) {
+            assert_eq!((tile.x, tile.y, tile.width, tile.height),
+                       (tile_mut.x, tile_mut.y, tile_mut.width(), tile_mut.height()));
+        }
+    }
+
+    #[test]
+    fn dilation_matches_masks_for_uneven_elements() {
+        let image = Image::from_fn(6, 6, |x, y| Rgba::gray(if (x, y) == (2, 3) { 1.0 } else { 0.0 }));
+        let element = StructuringElement::Rect(2, 3);
+        let dilated = CpuPipeline::default()
+            .dilate(element)
+            .apply(&image);
+        let mask = Mask::threshold(&image, 0.5).dilate(element);
+        assert!((0..6).all(|x| (0..6).all(|y| mask.get(x, y) == (dilated[(x, y)].red() == 1.0))));
+        assert!(mask.get(1, 2) && !mask.get(3, 2));
+    }
+
+    #[test]
+    fn opening_removes_specks_and_keeps_blocks() {
+        let image = Image::from_fn(9, 9, |x, y| Rgba::gray(if (x, y) == (1, 1) || (x >= 4 && y >= 4) { 1.0 } else { 0.0 }));
+        let out = CpuPipeline::default()
+            .open(StructuringElement::Rect(3, 3))
+            .apply(&image);
+        assert_eq!(out[(1, 1)].red(), 0.0);
+        assert_eq!(out[(4, 4)].red(), 1.0);
+        assert_eq!(out[(8, 8)].red(), 1.0);
+
+        // An even element neither shifts the block nor changes it when repeated
+        let element = StructuringElement::Rect(2, 2);
+        let opened = CpuPipeline::default()
+            .open(element)
+            .apply(&image);
+        let block = |x: usize, y: usize| if x >= 4 && y >= 4 { 1.0 } else { 0.0 };
+        assert!((0..9).all(|x| (0..9).all(|y| opened[(x, y)].red() == block(x, y))));
+        let twice = CpuPipeline::default()
+            .open(element)
+            .apply(&opened);
+        assert!((0..9).all(|x| (0..9).all(|y| twice[(x, y)].red() == opened[(x, y)].red())));
+        let closed = CpuPipeline::default()
+            .close(element)
+            .apply(&opened);
+        assert!((0..9).all(|x| (0..9).all(|y| closed[(x, y)].red() == block(x, y))));
+    }
+
+    #[test]
+    fn validation_follows_output_content() {
+        let info = ImageInfo::new(32, 32);
+        assert!(CpuPipeline::default().canny(0.02, 0.05).validate(&info).is_empty());
+        let issues = CpuPipeline::default().non_max_suppress().validate(&info);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Warning);
+        let issues = CpuPipeline::default().grayscale().skeletonize().validate(&info);
+        assert_eq!(issues[0].stage, 2);
+        let issues = CpuPipeline::default().canny_strength().gaussian_blur(4, 0.0).validate(&info);
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().all(|issue| issue.severity == Severity::Error && issue.stage == 5));
+        let issues = CpuPipeline::default()
+            .gradient_with(CpuGenerator::new(3).sobel_x(), Filter::Median(3), GradientNorm::L2, true)
+            .validate(&info);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn bad_parameters_are_validation_errors() {
+        let info = ImageInfo::new(32, 32);
+        let errors = |pipeline: CpuPipeline| pipeline.validate(&info)
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+        assert_eq!(errors(CpuPipeline::default().gamma(2.2)), 0);
+        assert_eq!(errors(CpuPipeline::default().gamma(0.0)), 1);
+        assert_eq!(errors(CpuPipeline::default().pad_to_aspect(-1.0, Rgba::gray(0.0))), 1);
+        assert_eq!(errors(CpuPipeline::default().decimate(0)), 1);
+        assert_eq!(errors(CpuPipeline::default().quantize_colors(0)), 1);
+        assert_eq!(errors(CpuPipeline::default().dither_palette(vec![])), 1);
+        assert_eq!(errors(CpuPipeline::default().frangi(vec![])), 1);
+        assert_eq!(errors(CpuPipeline::default().frangi(vec![1.0, 0.0])), 1);
+        let defocus = || CpuPipeline::default().filter(CpuGenerator::new(3).disk_needle(0.0));
+        assert_eq!(errors(defocus()), 1);
+        let image = Image::from_fn(4, 4, |x, y| Rgba::gray((x + y) as f64 / 6.0));
+        assert_eq!(defocus().apply(&image).width(), 4);
+    }
+
+    #[test]
+    fn weighted_box_blur_ignores_unweighted_pixels() {
+        let image = Image::from_fn(6, 6, |x, y| Rgba::gray(if (x, y) == (2, 3) { 1.0 } else { 0.25 }));
+        let weights = image.similar(|x, y| Rgba::gray(if (x, y) == (2, 3) { 0.0 } else { 1.0 }));
+        let blurred = CpuPipeline::default()
+            .with_weights(weights)
+            .average_blur(3)
+            .apply(&image);
+        assert!((0..6).all(|x| (0..6).all(|y| (blurred[(x, y)].red() - 0.25).abs() < 1e-12)));
+        let unweighted = CpuPipeline::default()
+            .average_blur(3)
+            .apply(&image);
+        assert!(unweighted[(2, 2)].red() > 0.3);
+    }
+
+    #[test]
+    fn dithering_keeps_the_mean_or_follows_the_matrix() {
+        let flat = |v: f64| Image::from_fn(16, 16, |_, _| Rgba::gray(v));
+        let diffused = CpuPipeline::default()
+            .dither(2, DitherMethod::FloydSteinberg)
+            .apply(&flat(0.25));
+        let values = (0..16)
+            .flat_map(|x| (0..16)
+                .map(move |y| (x, y)))
+            .map(|p| diffused[p].red())
+            .collect::<Vec<_>>();
+        assert!(values.iter().all(|v| *v == 0.0 || *v == 1.0));
+        assert!((values.iter().sum::<f64>() / 256.0 - 0.25).abs() < 0.02);
+
+        let ordered = |v: f64| CpuPipeline::default()
+            .dither(2, DitherMethod::Ordered(BayerSize::Two))
+            .apply(&flat(v));
+        let (quarter, half) = (ordered(0.25), ordered(0.5));
+        for (x, y) in (0..16).flat_map(|x| (0..16).map(move |y| (x, y))) {
+            assert_eq!(quarter[(x, y)].red(), if x % 2 == 0 && y % 2 == 1 { 1.0 } else { 0.0 });
+            assert_eq!(half[(x, y)].red(), ((x + y) % 2) as f64);
+        }
+    }
+
+    #[test]
+    fn despeckle_replaces_outliers_but_keeps_edges() {
+        // A step edge between columns 2 and 3, and a bright speck on the dark side
+        let image = Image::from_fn(7, 7, |x, y| Rgba::gray(match (x, y) {
+            (1, 4) => 1.0,
+            (0..=2, _) => 0.2,
+            _ => 0.8,
+        }));
+        let cleaned = CpuPipeline::default()
+            .despeckle(0.3)
+            .apply(&image);
+        assert_eq!(cleaned[(1, 4)].red(), 0.2);
+        for (x, y) in (0..7).flat_map(|x| (0..7).map(move |y| (x, y))).filter(|&p| p != (1, 4)) {
+            assert_eq!(cleaned[(x, y)].red(), image[(x, y)].red());
+        }
+    }
+
+    #[test]
+    fn fusion_keeps_identical_exposures_and_favours_the_exposed_one() {
+        let scene = Image::from_fn(16, 16, |x, y| Rgba::from((
+            0.3 + 0.4 * ((x + y) % 4) as f64 / 3.0,
+            0.5,
+            0.7 - 0.4 * (x % 3) as f64 / 2.0,
+            1.0,
+        )));
+        let fused = fuse_exposures(&[scene.clone(), scene.clone(), scene.clone()]);
+        assert!(mse(&fused, &scene) < 1e-20);
+
+        let dark = scene.similar(|x, y| scene[(x, y)].map_color(|v| v * 0.2));
+        let fused = fuse_exposures(&[dark.clone(), scene.clone()]);
+        let averaged = scene.similar(|x, y| (scene[(x, y)] + dark[(x, y)]) / 2.0);
+        assert!(mse(&fused, &scene) < mse(&averaged, &scene) / 4.0);
+    }
+
+    #[test]
+    fn shifted_frames_stack_back_onto_the_reference() {
+        let texture = |x: i64, y: i64| Rgba::gray(((x * 37 + y * 91 + x * y * 13).rem_euclid(17)) as f64 / 16.0);
+        let reference = Image::from_fn(32, 32, |x, y| texture(x as i64, y as i64));
+        let shifted = Image::from_fn(32, 32, |x, y| texture(x as i64 - 3, y as i64 - 2));
+        assert_eq!(phase_correlation(&reference, &shifted), (3, 2));
+        for stacking in [Stacking::Mean, Stacking::Median] {
+            let stacked = average_aligned(&[reference.clone(), shifted.clone(), shifted.clone()], stacking);
+            // The shifted frames are clamped at their right and bottom edges, so only this part lines up
+            for (x, y) in (0..29).flat_map(|x| (0..30).map(move |y| (x, y))) {
+                assert!((stacked[(x, y)].red() - reference[(x, y)].red()).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn cvd_keeps_grays_and_darkens_red_for_protanopes() {
+        let image = Image::from_fn(3, 1, |x, _| match x {
+            0 => Rgba::gray(0.5),
+            1 => Rgba::gray(0.9),
+            _ => Rgba::from((1.0, 0.0, 0.0, 1.0)),
+        });
+        for deficiency in [ColorVisionDeficiency::Protanopia, ColorVisionDeficiency::Deuteranopia, ColorVisionDeficiency::Tritanopia] {
+            let simulated = CpuPipeline::default()
+                .simulate_cvd(deficiency)
+                .apply(&image);
+            for x in 0..2 {
+                let [r, g, b, _]: [f64; 4] = simulated[(x, 0)].into();
+                let gray = image[(x, 0)].red();
+                assert!([r, g, b].iter().all(|c| (c - gray).abs() < 1e-5));
+            }
+        }
+        // Linear red becomes (0.152286, 0.114503, -0.003882), clipped and re-encoded as sRGB
+        let red = CpuPipeline::default()
+            .simulate_cvd(ColorVisionDeficiency::Protanopia)
+            .apply(&image)[(2, 0)];
+        assert!((red.red() - 0.426608).abs() < 1e-5);
+        assert!((red.green() - 0.372654).abs() < 1e-5);
+        assert_eq!(red.blue(), 0.0);
+    }
+
+    #[test]
+    fn skeletonize_thins_a_bar_to_a_line() {
+        let bar = Image::from_fn(12, 7, |x, y| Rgba::gray(if (2..10).contains(&x) && (2..5).contains(&y) { 1.0 } else { 0.0 }));
+        let skeleton = CpuPipeline::default()
+            .skeletonize()
+            .apply(&bar);
+        let on = (0..12)
+            .flat_map(|x| (0..7)
+                .map(move |y| (x, y)))
+            .filter(|&p| skeleton[p].intensity() > 0.5)
+            .collect::<Vec<_>>();
+        // One pixel thick along the middle row, without gaps
+        assert!(on.len() >= 4);
+        assert!(on.iter().all(|&(_, y)| y == 3));
+        assert!(on.windows(2).all(|pair| pair[1].0 == pair[0].0 + 1));
+    }
+
+    #[test]
+    fn auto_canny_finds_the_border_of_a_square() {
+        let square = Image::from_fn(24, 24, |x, y| Rgba::gray(if (6..18).contains(&x) && (6..18).contains(&y) { 0.9 } else { 0.1 }));
+        let edges = CpuPipeline::default()
+            .auto_canny()
+            .apply(&square);
+        let on = |x: usize, y: usize| edges[(x, y)].intensity() > 0.5;
+        assert!((0..24).all(|x| (0..24).all(|y| [0.0, 1.0].contains(&edges[(x, y)].intensity()))));
+        // Every row crossing the square has an edge at each of its sides, and flat areas have none
+        assert!((8..16).all(|y| (4..8).any(|x| on(x, y)) && (16..20).any(|x| on(x, y))));
+        assert!((0..24).all(|x| (0..3).all(|y| !on(x, y))));
+        assert!((10..14).all(|x| (10..14).all(|y| !on(x, y))));
+    }
+}
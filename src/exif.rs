@@ -0,0 +1,103 @@
+use std::path::Path;
+use crate::Orientation;
+
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// The EXIF orientation of a JPEG file, if it has one
+pub fn read_orientation(path: impl AsRef<Path>) -> Option<Orientation> {
+    jpeg_orientation(&std::fs::read(path).ok()?)
+}
+
+/// Walks the JPEG segments up to the image data looking for an EXIF block
+pub fn jpeg_orientation(data: &[u8]) -> Option<Orientation> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut i = 2;
+    while i + 4 <= data.len() && data[i] == 0xFF {
+        let marker = data[i + 1];
+        // Start of scan or end of image, no metadata after these
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        let segment = data.get(i + 4..i + 2 + length)?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return tiff_orientation(&segment[6..]);
+        }
+        i += 2 + length;
+    }
+    None
+}
+
+/// Looks the orientation tag up in the first IFD of a TIFF header
+fn tiff_orientation(tiff: &[u8]) -> Option<Orientation> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| tiff.get(at..at + 2)
+        .map(|b| if little_endian {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        });
+    let u32_at = |at: usize| tiff.get(at..at + 4)
+        .map(|b| if little_endian {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        });
+
+    if u16_at(2)? != 42 {
+        return None;
+    }
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries)
+        .map(|n| ifd + 2 + 12 * n)
+        .find(|entry| u16_at(*entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .and_then(Orientation::from_exif)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Orientation;
+    use super::jpeg_orientation;
+
+    /// A JPEG header with a comment segment, then an EXIF segment holding a
+    /// single IFD entry for the orientation
+    fn jpeg(big_endian: bool, orientation: u16) -> Vec<u8> {
+        let u16_bytes = |v: u16| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let u32_bytes = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut tiff = if big_endian { b"MM".to_vec() } else { b"II".to_vec() };
+        tiff.extend(u16_bytes(42));
+        tiff.extend(u32_bytes(8));
+        tiff.extend(u16_bytes(1));
+        tiff.extend(u16_bytes(0x0112));
+        tiff.extend(u16_bytes(3));
+        tiff.extend(u32_bytes(1));
+        tiff.extend(u16_bytes(orientation));
+        tiff.extend([0, 0]);
+        tiff.extend(u32_bytes(0));
+
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xFE, 0x00, 0x04, b'h', b'i', 0xFF, 0xE1];
+        data.extend(((2 + 6 + tiff.len()) as u16).to_be_bytes());
+        data.extend(b"Exif\0\0");
+        data.extend(tiff);
+        data.extend([0xFF, 0xDA]);
+        data
+    }
+
+    #[test]
+    fn reads_orientation_in_both_byte_orders() {
+        assert_eq!(jpeg_orientation(&jpeg(false, 6)), Some(Orientation::Rotate90));
+        assert_eq!(jpeg_orientation(&jpeg(true, 3)), Some(Orientation::Rotate180));
+        assert_eq!(jpeg_orientation(&jpeg(true, 9)), None);
+        assert_eq!(jpeg_orientation(&[0x89, b'P', b'N', b'G']), None);
+        // Cut off in the middle of the EXIF segment
+        assert_eq!(jpeg_orientation(&jpeg(false, 6)[..20]), None);
+    }
+}
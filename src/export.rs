@@ -0,0 +1,573 @@
+use std::fmt::Write;
+use crate::contours::Contour;
+use crate::cpu::Image;
+use crate::labels::RegionProps;
+use crate::lsd::LineSegment;
+
+pub type Polyline = Vec<(usize, usize)>;
+
+/// Links the pixels of an edge map brighter than `threshold` into 8-connected
+/// chains. Chains start at loose ends where possible, so an open curve comes
+/// out as a single polyline
+pub fn trace_polylines(edges: &Image, threshold: f64) -> Vec<Polyline> {
+    let (width, height) = (edges.width(), edges.height());
+    let on = |x: usize, y: usize| edges[(x, y)].intensity() > threshold;
+    // Orthogonal neighbours first, so chains don't cut corners
+    let neighbours = move |x: usize, y: usize| [(0, -1), (1, 0), (0, 1), (-1, 0), (1, -1), (1, 1), (-1, 1), (-1, -1)]
+        .into_iter()
+        .map(move |(i, j)| (x as i64 + i, y as i64 + j))
+        .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width as i64 && ny < height as i64)
+        .map(|(nx, ny)| (nx as usize, ny as usize))
+        .filter(move |&(nx, ny)| on(nx, ny));
+
+    let pixels = (0..height)
+        .flat_map(|y| (0..width)
+            .map(move |x| (x, y)))
+        .filter(|&(x, y)| on(x, y))
+        .collect::<Vec<_>>();
+    let ends = pixels.iter()
+        .copied()
+        .filter(|&(x, y)| neighbours(x, y).count() <= 1);
+
+    let mut visited = vec![vec![false; height]; width];
+    let mut polylines = vec![];
+    for (x, y) in ends.chain(pixels.iter().copied()).collect::<Vec<_>>() {
+        if visited[x][y] {
+            continue;
+        }
+        visited[x][y] = true;
+        let mut polyline = vec![(x, y)];
+        while let Some((nx, ny)) = polyline.last()
+            .and_then(|&(x, y)| neighbours(x, y).find(|&(nx, ny)| !visited[nx][ny])) {
+            visited[nx][ny] = true;
+            polyline.push((nx, ny));
+        }
+        polylines.push(polyline);
+    }
+    polylines
+}
+
+/// An SVG document drawing every polyline as a path through pixel centres
+pub fn polylines_to_svg(polylines: &[Polyline], width: usize, height: usize) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    svg.push_str("<path fill=\"none\" stroke=\"black\" stroke-width=\"1\" stroke-linecap=\"round\" stroke-linejoin=\"round\" d=\"");
+    for polyline in polylines.iter().filter(|p| !p.is_empty()) {
+        let (x, y) = polyline[0];
+        write!(svg, "M{}.5 {}.5", x, y).unwrap();
+        if polyline.len() == 1 {
+            svg.push_str("h0");
+        }
+        for (x, y) in &polyline[1..] {
+            write!(svg, "L{}.5 {}.5", x, y).unwrap();
+        }
+    }
+    svg.push_str("\"/>\n</svg>\n");
+    svg
+}
+
+/// Traces a binary edge map, such as the output of `canny`, into an SVG document
+pub fn edges_to_svg(edges: &Image) -> String {
+    polylines_to_svg(&trace_polylines(edges, 0.5), edges.width(), edges.height())
+}
+
+/// Shape of a detection in pixel coordinates, `y` pointing down
+#[derive(Clone, Debug, PartialEq)]
+pub enum Geometry {
+    Point(f64, f64),
+    LineString(Vec<(f64, f64)>),
+    /// A closed ring, without the first point repeated at the end
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Geometry {
+    fn vertices(&self) -> Vec<(f64, f64)> {
+        match self {
+            Geometry::Point(x, y) => vec![(*x, *y)],
+            Geometry::LineString(points) | Geometry::Polygon(points) => points.clone(),
+        }
+    }
+
+    /// The shape GeoJSON can hold: lines need two distinct positions and
+    /// rings three, so fewer become a line or a point. `None` when empty
+    fn valid(self) -> Option<Geometry> {
+        let mut distinct = vec![];
+        for point in self.vertices() {
+            if !distinct.contains(&point) {
+                distinct.push(point);
+            }
+        }
+        match (self, distinct.len()) {
+            (_, 0) => None,
+            (_, 1) => Some(Geometry::Point(distinct[0].0, distinct[0].1)),
+            (Geometry::Polygon(_), 2) => Some(Geometry::LineString(distinct)),
+            (geometry, _) => Some(geometry),
+        }
+    }
+}
+
+/// A detection result that can be written as GeoJSON or CSV, for other
+/// tools or for `from_geojson` and `from_csv` to read back
+pub trait Detection {
+    fn geometry(&self) -> Geometry;
+    /// Named measurements, `None` where there is no value
+    fn properties(&self) -> Vec<(&'static str, Option<f64>)>;
+}
+
+fn to_points(points: &[(usize, usize)]) -> Vec<(f64, f64)> {
+    points.iter()
+        .map(|&(x, y)| (x as f64, y as f64))
+        .collect()
+}
+
+impl Detection for Polyline {
+    fn geometry(&self) -> Geometry {
+        Geometry::LineString(to_points(self))
+    }
+
+    fn properties(&self) -> Vec<(&'static str, Option<f64>)> {
+        vec![]
+    }
+}
+
+impl Detection for LineSegment {
+    fn geometry(&self) -> Geometry {
+        Geometry::LineString(vec![self.start, self.end])
+    }
+
+    fn properties(&self) -> Vec<(&'static str, Option<f64>)> {
+        vec![("width", Some(self.width)), ("significance", Some(self.significance))]
+    }
+}
+
+impl Detection for Contour {
+    fn geometry(&self) -> Geometry {
+        Geometry::Polygon(to_points(&self.points))
+    }
+
+    fn properties(&self) -> Vec<(&'static str, Option<f64>)> {
+        vec![("hole", Some(self.hole as u8 as f64)), ("parent", self.parent.map(|p| p as f64))]
+    }
+}
+
+impl Detection for RegionProps {
+    fn geometry(&self) -> Geometry {
+        Geometry::Point(self.centroid.0, self.centroid.1)
+    }
+
+    fn properties(&self) -> Vec<(&'static str, Option<f64>)> {
+        vec![
+            ("label", Some(self.label as f64)),
+            ("area", Some(self.area as f64)),
+            ("perimeter", Some(self.perimeter as f64)),
+            ("eccentricity", Some(self.eccentricity)),
+            ("orientation", Some(self.orientation)),
+        ]
+    }
+}
+
+fn json_number(value: Option<f64>) -> String {
+    match value {
+        Some(v) if v.is_finite() => v.to_string(),
+        _ => "null".to_string(),
+    }
+}
+
+fn json_position(x: f64, y: f64) -> String {
+    format!("[{},{}]", json_number(Some(x)), json_number(Some(y)))
+}
+
+fn json_positions(points: &[(f64, f64)]) -> String {
+    let positions = points.iter()
+        .map(|&(x, y)| json_position(x, y))
+        .collect::<Vec<_>>();
+    format!("[{}]", positions.join(","))
+}
+
+/// A GeoJSON feature collection with one feature per detection. Lines of a
+/// single point and rings of fewer than three are written as the simpler
+/// geometry, and detections without any vertices are left out
+pub fn to_geojson<D: Detection>(detections: &[D]) -> String {
+    let features = detections.iter()
+        .filter_map(|detection| Some((detection, detection.geometry().valid()?)))
+        .map(|(detection, geometry)| {
+            let geometry = match geometry {
+                Geometry::Point(x, y) =>
+                    format!("{{\"type\":\"Point\",\"coordinates\":{}}}", json_position(x, y)),
+                Geometry::LineString(points) =>
+                    format!("{{\"type\":\"LineString\",\"coordinates\":{}}}", json_positions(&points)),
+                Geometry::Polygon(mut points) => {
+                    if let Some(&first) = points.first() {
+                        points.push(first);
+                    }
+                    format!("{{\"type\":\"Polygon\",\"coordinates\":[{}]}}", json_positions(&points))
+                },
+            };
+            let properties = detection.properties()
+                .into_iter()
+                .map(|(name, value)| format!("\"{}\":{}", name, json_number(value)))
+                .collect::<Vec<_>>();
+            format!("{{\"type\":\"Feature\",\"geometry\":{},\"properties\":{{{}}}}}", geometry, properties.join(","))
+        })
+        .collect::<Vec<_>>();
+    format!("{{\"type\":\"FeatureCollection\",\"features\":[{}]}}\n", features.join(","))
+}
+
+/// CSV with a header and one row per vertex, as `id,x,y` followed by the
+/// detection's properties. Rows of the same detection share its `id`
+pub fn to_csv<D: Detection>(detections: &[D]) -> String {
+    let names = detections.first()
+        .map(|detection| detection.properties()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut csv = ["id", "x", "y"].iter()
+        .chain(&names)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(",");
+    csv.push('\n');
+    for (id, detection) in detections.iter().enumerate() {
+        let properties = detection.properties()
+            .into_iter()
+            .map(|(_, value)| value.map_or(String::new(), |v| v.to_string()))
+            .collect::<Vec<_>>();
+        for (x, y) in detection.geometry().vertices() {
+            write!(csv, "{},{},{}", id, x, y).unwrap();
+            for value in &properties {
+                write!(csv, ",{}", value).unwrap();
+            }
+            csv.push('\n');
+        }
+    }
+    csv
+}
+
+/// A detection as read back by `from_geojson` or `from_csv`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Feature {
+    pub geometry: Geometry,
+    pub properties: Vec<(String, Option<f64>)>,
+}
+
+/// The subset of JSON that GeoJSON needs
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn array(&self) -> Result<&[Json], String> {
+        match self {
+            Json::Array(items) => Ok(items),
+            _ => Err("Expected an array".to_string()),
+        }
+    }
+
+    fn position(&self) -> Result<(f64, f64), String> {
+        match self.array()? {
+            [Json::Number(x), Json::Number(y), ..] => Ok((*x, *y)),
+            _ => Err("Expected a position of two numbers".to_string()),
+        }
+    }
+
+    fn positions(&self) -> Result<Vec<(f64, f64)>, String> {
+        self.array()?
+            .iter()
+            .map(Json::position)
+            .collect()
+    }
+}
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl JsonParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.text.get(self.at) != Some(&byte) {
+            return Err(format!("Expected '{}' at byte {}", byte as char, self.at));
+        }
+        self.at += 1;
+        Ok(())
+    }
+
+    /// Parses a list of `item` up to `close`, after its opening bracket
+    fn list<T>(&mut self, close: u8, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.text.get(self.at) == Some(&close) {
+            self.at += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            self.skip_whitespace();
+            match self.text.get(self.at) {
+                Some(b',') => self.at += 1,
+                Some(&b) if b == close => {
+                    self.at += 1;
+                    return Ok(items);
+                },
+                _ => return Err(format!("Expected ',' or '{}' at byte {}", close as char, self.at)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut text = String::new();
+        let mut chars = std::str::from_utf8(&self.text[self.at..])
+            .map_err(|_| "Invalid UTF-8".to_string())?
+            .char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.at += i + 1;
+                    return Ok(text);
+                },
+                '\\' => text.push(match chars.next().map(|(_, c)| c) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('u') => {
+                        let code = (0..4)
+                            .filter_map(|_| chars.next().map(|(_, c)| c))
+                            .collect::<String>();
+                        u32::from_str_radix(&code, 16).ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid escape '\\u{code}'"))?
+                    },
+                    Some(c) => c,
+                    None => break,
+                }),
+                c => text.push(c),
+            }
+        }
+        Err("Unterminated string".to_string())
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        let rest = &self.text[self.at..];
+        let literal = |word: &str, value: Json| rest.starts_with(word.as_bytes())
+            .then_some((word.len(), value));
+        if let Some((length, value)) = literal("null", Json::Null)
+            .or_else(|| literal("true", Json::Bool(true)))
+            .or_else(|| literal("false", Json::Bool(false))) {
+            self.at += length;
+            return Ok(value);
+        }
+        match rest.first() {
+            Some(b'"') => self.string().map(Json::Text),
+            Some(b'[') => {
+                self.at += 1;
+                self.list(b']', Self::value).map(Json::Array)
+            },
+            Some(b'{') => {
+                self.at += 1;
+                self.list(b'}', |parser| {
+                    let name = parser.string()?;
+                    parser.expect(b':')?;
+                    Ok((name, parser.value()?))
+                }).map(Json::Object)
+            },
+            _ => {
+                let length = rest.iter()
+                    .take_while(|b| b.is_ascii_digit() || b"+-.eE".contains(b))
+                    .count();
+                let number = std::str::from_utf8(&rest[..length]).unwrap_or_default();
+                self.at += length;
+                number.parse()
+                    .map(Json::Number)
+                    .map_err(|_| format!("Expected a value at byte {}", self.at - length))
+            },
+        }
+    }
+}
+
+/// Reads the features of a GeoJSON feature collection with `Point`,
+/// `LineString` and `Polygon` geometries, such as written by `to_geojson`.
+/// Only the outer ring of a polygon is kept. Boolean properties come back
+/// as `0` or `1`, and other properties that are not numbers as `None`
+pub fn from_geojson(text: &str) -> Result<Vec<Feature>, String> {
+    let mut parser = JsonParser { text: text.as_bytes(), at: 0 };
+    let collection = parser.value()?;
+    collection.get("features")
+        .ok_or("Expected a feature collection")?
+        .array()?
+        .iter()
+        .map(|feature| {
+            let geometry = feature.get("geometry").ok_or("Feature without a geometry")?;
+            let coordinates = geometry.get("coordinates").ok_or("Geometry without coordinates")?;
+            let geometry = match geometry.get("type") {
+                Some(Json::Text(kind)) if kind == "Point" => {
+                    let (x, y) = coordinates.position()?;
+                    Geometry::Point(x, y)
+                },
+                Some(Json::Text(kind)) if kind == "LineString" => Geometry::LineString(coordinates.positions()?),
+                Some(Json::Text(kind)) if kind == "Polygon" => {
+                    let mut ring = coordinates.array()?
+                        .first()
+                        .ok_or("Polygon without a ring")?
+                        .positions()?;
+                    if ring.len() > 1 && ring.first() == ring.last() {
+                        ring.pop();
+                    }
+                    Geometry::Polygon(ring)
+                },
+                _ => return Err("Unsupported geometry type".to_string()),
+            };
+            let properties = match feature.get("properties") {
+                Some(Json::Object(members)) => members.iter()
+                    .map(|(name, value)| (name.clone(), match value {
+                        Json::Number(v) => Some(*v),
+                        Json::Bool(b) => Some(*b as u8 as f64),
+                        _ => None,
+                    }))
+                    .collect(),
+                _ => vec![],
+            };
+            Ok(Feature { geometry, properties })
+        })
+        .collect()
+}
+
+/// Reads the rows written by `to_csv`, one feature per `id`. CSV does not
+/// record the kind of geometry, so a single vertex comes back as a `Point`
+/// and several as a `LineString`. Empty property cells are `None`
+pub fn from_csv(text: &str) -> Result<Vec<Feature>, String> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next()
+        .ok_or("The CSV is empty")?
+        .split(',')
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    if header.get(..3) != Some(&["id", "x", "y"][..]) {
+        return Err("Expected the columns to start with id,x,y".to_string());
+    }
+    let number = |cell: &str| cell.trim()
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid number '{cell}'"));
+    let mut features: Vec<(&str, Feature)> = vec![];
+    for (n, line) in lines.enumerate() {
+        let cells = line.split(',')
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        if cells.len() != header.len() {
+            return Err(format!("Row {} has {} cells, expected {}", n + 1, cells.len(), header.len()));
+        }
+        let vertex = (number(cells[1])?, number(cells[2])?);
+        match features.last_mut() {
+            Some((id, Feature { geometry: Geometry::LineString(vertices), .. })) if *id == cells[0] =>
+                vertices.push(vertex),
+            _ => {
+                let properties = header[3..].iter()
+                    .zip(&cells[3..])
+                    .map(|(name, cell)| Ok((name.to_string(), (!cell.is_empty()).then(|| number(cell)).transpose()?)))
+                    .collect::<Result<Vec<_>, String>>()?;
+                features.push((cells[0], Feature { geometry: Geometry::LineString(vec![vertex]), properties }));
+            },
+        }
+    }
+    Ok(features.into_iter()
+        .map(|(_, mut feature)| {
+            if let Geometry::LineString(vertices) = &feature.geometry {
+                if let [(x, y)] = vertices[..] {
+                    feature.geometry = Geometry::Point(x, y);
+                }
+            }
+            feature
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contours::Contour;
+    use crate::labels::RegionProps;
+    use super::{from_csv, from_geojson, to_csv, to_geojson, Feature, Geometry, Polyline};
+
+    #[test]
+    fn polylines_export_every_vertex() {
+        let polylines: Vec<Polyline> = vec![vec![(0, 0), (1, 1)], vec![(3, 2)]];
+        assert_eq!(to_csv(&polylines), "id,x,y\n0,0,0\n0,1,1\n1,3,2\n");
+        assert_eq!(
+            to_geojson(&polylines[1..]),
+            "{\"type\":\"FeatureCollection\",\"features\":[{\"type\":\"Feature\",\"geometry\":{\"type\":\"Point\",\"coordinates\":[3,2]},\"properties\":{}}]}\n"
+        );
+    }
+
+    #[test]
+    fn small_contours_stay_valid_geojson() {
+        let contour = |points: Vec<(usize, usize)>| Contour { points, hole: false, parent: None };
+        let geometry = |contour: Contour| {
+            let json = to_geojson(&[contour]);
+            let start = json.find("\"geometry\":").unwrap();
+            let end = json.find(",\"properties\"").unwrap();
+            json[start + 11..end].to_string()
+        };
+        assert_eq!(geometry(contour(vec![(1, 1), (2, 1), (2, 2)])),
+                   "{\"type\":\"Polygon\",\"coordinates\":[[[1,1],[2,1],[2,2],[1,1]]]}");
+        assert_eq!(geometry(contour(vec![(1, 1), (2, 1), (1, 1)])),
+                   "{\"type\":\"LineString\",\"coordinates\":[[1,1],[2,1]]}");
+        assert_eq!(geometry(contour(vec![(4, 0)])), "{\"type\":\"Point\",\"coordinates\":[4,0]}");
+        assert!(!to_geojson(&[contour(vec![])]).contains("Feature\""));
+    }
+
+    #[test]
+    fn exports_read_back() {
+        let contours = vec![
+            Contour { points: vec![(1, 1), (4, 1), (4, 3)], hole: false, parent: None },
+            Contour { points: vec![(2, 2)], hole: true, parent: Some(0) },
+        ];
+        let features = from_geojson(&to_geojson(&contours)).unwrap();
+        assert_eq!(features, vec![
+            Feature {
+                geometry: Geometry::Polygon(vec![(1.0, 1.0), (4.0, 1.0), (4.0, 3.0)]),
+                properties: vec![("hole".to_string(), Some(0.0)), ("parent".to_string(), None)],
+            },
+            Feature {
+                geometry: Geometry::Point(2.0, 2.0),
+                properties: vec![("hole".to_string(), Some(1.0)), ("parent".to_string(), Some(0.0))],
+            },
+        ]);
+
+        let features = from_csv(&to_csv(&contours)).unwrap();
+        assert_eq!(features[0].geometry, Geometry::LineString(vec![(1.0, 1.0), (4.0, 1.0), (4.0, 3.0)]));
+        assert_eq!(features[1].properties, vec![("hole".to_string(), Some(1.0)), ("parent".to_string(), Some(0.0))]);
+        assert_eq!(features[0].properties[1], ("parent".to_string(), None));
+
+        let region = RegionProps { label: 1, area: 6, centroid: (2.0, 1.5), perimeter: 10, eccentricity: 0.5, orientation: -0.25 };
+        let features = from_geojson(&to_geojson(&[region])).unwrap();
+        assert_eq!(features[0].geometry, Geometry::Point(2.0, 1.5));
+        assert_eq!(features[0].properties[4], ("orientation".to_string(), Some(-0.25)));
+        assert!(from_geojson("{\"type\":\"FeatureCollection\",\"features\":[{]}").is_err());
+        assert!(from_csv("x,y\n1,2\n").is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use std::f64::consts::PI;
+use crate::cpu::Image;
+use crate::pipeline::Image as _;
+use crate::rgba::Rgba;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex {
+        re: 0.0,
+        im: 0.0
+    };
+
+    pub fn new(re: f64, im: f64) -> Self {
+        Complex {
+            re,
+            im
+        }
+    }
+
+    pub fn from_polar(norm: f64, arg: f64) -> Self {
+        Complex::new(norm * arg.cos(), norm * arg.sin())
+    }
+
+    pub fn conj(self) -> Self {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn scale(self, factor: f64) -> Self {
+        Complex::new(self.re * factor, self.im * factor)
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Complex::new(re, 0.0)
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re * rhs.re - self.im * rhs.im,
+                     self.re * rhs.im + self.im * rhs.re)
+    }
+}
+
+/// In-place radix-2 FFT. The length of `data` must be a power of two.
+/// The inverse transform is normalized by `1 / len`
+pub fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    assert!(n.is_power_of_two(), "FFT length must be a power of two, got {n}");
+
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits().checked_shr(usize::BITS - bits).unwrap_or(0);
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let step = Complex::from_polar(1.0, sign * 2.0 * PI / len as f64);
+        for start in (0..n).step_by(len) {
+            let mut w = Complex::from(1.0);
+            for k in 0..len / 2 {
+                let even = data[start + k];
+                let odd = data[start + k + len / 2] * w;
+                data[start + k] = even + odd;
+                data[start + k + len / 2] = even - odd;
+                w = w * step;
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        data.iter_mut()
+            .for_each(|c| *c = c.scale(1.0 / n as f64));
+    }
+}
+
+/// 2D FFT over a grid indexed as `[x][y]`, like the images.
+/// Both dimensions must be powers of two
+pub fn fft2d(data: &mut [Vec<Complex>], inverse: bool) {
+    data.iter_mut()
+        .for_each(|column| fft(column, inverse));
+
+    let height = data.first().map(Vec::len).unwrap_or(0);
+    let mut row = vec![Complex::ZERO; data.len()];
+    for y in 0..height {
+        for (value, column) in row.iter_mut().zip(data.iter()) {
+            *value = column[y];
+        }
+        fft(&mut row, inverse);
+        for (value, column) in row.iter().zip(data.iter_mut()) {
+            column[y] = *value;
+        }
+    }
+}
+
+/// The 2D FFT of every channel of an image, zero padded to powers of two
+#[derive(Clone, Debug)]
+pub struct Spectrum {
+    /// Size of the transformed image, before padding
+    pub width: usize,
+    pub height: usize,
+    /// Red, green, blue and alpha, each indexed as `[u][v]`
+    pub channels: [Vec<Vec<Complex>>; 4],
+}
+
+impl Spectrum {
+    pub fn forward(image: &Image) -> Spectrum {
+        let (width, height) = (image.width(), image.height());
+        let (padded_width, padded_height) = (width.next_power_of_two(), height.next_power_of_two());
+        let channel = |c: usize| {
+            let mut data = (0..padded_width)
+                .map(|x| (0..padded_height)
+                    .map(|y| if x < width && y < height {
+                        let pixel: [f64; 4] = image[(x, y)].into();
+                        pixel[c].into()
+                    } else {
+                        Complex::ZERO
+                    })
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            fft2d(&mut data, false);
+            data
+        };
+        Spectrum {
+            width,
+            height,
+            channels: [channel(0), channel(1), channel(2), channel(3)],
+        }
+    }
+
+    /// Transforms back and crops the padding away
+    pub fn inverse(&self) -> Image {
+        let channels = self.channels.clone()
+            .map(|mut data| {
+                fft2d(&mut data, true);
+                data
+            });
+        Image::from_fn(self.width, self.height, |x, y| channels.iter()
+            .map(|data| data[x][y].re)
+            .collect())
+    }
+
+    /// Size of the padded transform
+    pub fn size(&self) -> (usize, usize) {
+        let width = self.channels[0].len();
+        (width, self.channels[0].first().map_or(0, Vec::len))
+    }
+
+    /// The signed frequency of bin `(u, v)` in cycles per pixel, each in [-0.5, 0.5)
+    pub fn frequency(&self, u: usize, v: usize) -> (f64, f64) {
+        let (width, height) = self.size();
+        let signed = |k: usize, n: usize| if k >= n.div_ceil(2) {
+            k as f64 - n as f64
+        } else {
+            k as f64
+        } / n as f64;
+        (signed(u, width), signed(v, height))
+    }
+
+    /// Scales every bin of the colour channels by the gain for its signed
+    /// frequency, as given by `frequency`. Alpha is left as it is
+    pub fn filter(&mut self, gain: impl Fn(f64, f64) -> f64) {
+        let (width, height) = self.size();
+        for u in 0..width {
+            for v in 0..height {
+                let (fu, fv) = self.frequency(u, v);
+                let gain = gain(fu, fv);
+                for channel in &mut self.channels[..3] {
+                    channel[u][v] = channel[u][v].scale(gain);
+                }
+            }
+        }
+    }
+
+    /// Moves the zero frequency from the corner to the centre, for display
+    fn shifted(&self, f: impl Fn(usize, usize, usize) -> f64) -> Image {
+        let (width, height) = self.size();
+        Image::from_fn(width, height, |x, y| {
+            let (u, v) = ((x + width / 2) % width, (y + height / 2) % height);
+            Rgba::from((f(0, u, v), f(1, u, v), f(2, u, v), 1.0))
+        })
+    }
+
+    /// Log magnitude of the colour channels, scaled to [0, 1], with the
+    /// zero frequency in the centre
+    pub fn magnitude_image(&self) -> Image {
+        let log = |c: usize, u: usize, v: usize| self.channels[c][u][v].norm().ln_1p();
+        let (width, height) = self.size();
+        let max = (0..3)
+            .flat_map(|c| (0..width)
+                .flat_map(move |u| (0..height)
+                    .map(move |v| log(c, u, v))))
+            .fold(0.0, f64::max)
+            .max(f64::EPSILON);
+        self.shifted(|c, u, v| log(c, u, v) / max)
+    }
+
+    /// Phase of the colour channels, from -pi at `0.0` to pi at `1.0`, with
+    /// the zero frequency in the centre
+    pub fn phase_image(&self) -> Image {
+        self.shifted(|c, u, v| (self.channels[c][u][v].arg() + PI) / (2.0 * PI))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::Spectrum;
+
+    #[test]
+    fn inverse_undoes_forward() {
+        let image = Image::from_fn(5, 3, |x, y| Rgba::from((x as f64 / 5.0, y as f64 / 3.0, 0.5, 1.0)));
+        let spectrum = Spectrum::forward(&image);
+        assert_eq!(spectrum.size(), (8, 4));
+        // The zero frequency holds the sum of the channel
+        assert!((spectrum.channels[2][0][0].re - 7.5).abs() < 1e-9);
+        let back = spectrum.inverse();
+        for x in 0..5 {
+            for y in 0..3 {
+                assert!((back[(x, y)].red() - image[(x, y)].red()).abs() < 1e-9);
+                assert!((back[(x, y)].alpha() - 1.0).abs() < 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::cpu::Image;
+use crate::rgba::Rgba;
+
+/// Size of a glyph in font pixels
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// Rows of a glyph from the top, three bits each with the leftmost pixel in
+/// the highest bit. Lowercase letters share the uppercase glyphs
+fn glyph(c: char) -> Option<[u8; GLYPH_HEIGHT]> {
+    Some(match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b111, 0b001, 0b010, 0b000, 0b010],
+        _ => return None,
+    })
+}
+
+/// Width in pixels of `text` drawn at `scale`, glyphs being one font pixel apart
+pub fn text_width(text: &str, scale: usize) -> usize {
+    (text.chars().count() * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+/// Draws `text` in a 3x5 pixel font with its top left corner at `(left, top)`,
+/// every font pixel becoming a `scale` by `scale` square of `color`.
+/// Characters without a glyph are drawn as `?`, and whatever falls outside
+/// the image is clipped
+pub fn draw_text(image: &mut Image, text: &str, left: usize, top: usize, scale: usize, color: Rgba) {
+    let (width, height) = (image.width(), image.height());
+    for (n, c) in text.chars().enumerate() {
+        let rows = glyph(c).or_else(|| glyph('?')).unwrap();
+        let glyph_left = left + n * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in (0..GLYPH_WIDTH).filter(|column| bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0) {
+                let (x0, y0) = (glyph_left + column * scale, top + row * scale);
+                for x in x0..(x0 + scale).min(width) {
+                    for y in y0..(y0 + scale).min(height) {
+                        image[(x, y)] = color;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::rgba::Rgba;
+    use super::{draw_text, text_width};
+
+    #[test]
+    fn draws_scaled_and_clipped_glyphs() {
+        assert_eq!(text_width("", 2), 0);
+        assert_eq!(text_width("ab", 2), 14);
+
+        let mut image = Image::empty(7, 5);
+        draw_text(&mut image, "1l", 0, 0, 1, Rgba::WHITE);
+        let on = |image: &Image, x: usize, y: usize| image[(x, y)].red() == 1.0;
+        // The stem of the 1 and the foot of the L
+        assert!((0..5).all(|y| on(&image, 1, y)));
+        assert!(!on(&image, 0, 0) && on(&image, 0, 1));
+        assert!((4..7).all(|x| on(&image, x, 4)) && !on(&image, 5, 3));
+        assert!((0..5).all(|y| !on(&image, 3, y)));
+
+        let mut image = Image::empty(5, 3);
+        draw_text(&mut image, "~", 0, 0, 2, Rgba::WHITE);
+        // Drawn as a question mark, cut at the right and bottom edges
+        assert!((0..5).all(|x| on(&image, x, 0) && on(&image, x, 1)));
+        assert!(!on(&image, 0, 2) && on(&image, 4, 2));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::cpu::{CpuPipeline, Image};
+use crate::pipeline::{Image as _, Pipeline};
+use crate::rgba::Rgba;
+
+/// Thresholds the golden outputs are computed with
+pub const CANNY_LOW: f64 = 0.015;
+pub const CANNY_HIGH: f64 = 0.03;
+
+const SIZE: usize = 48;
+
+/// Edge hashes of the CPU `canny(CANNY_LOW, CANNY_HIGH)` output for every pattern
+pub const EXPECTED: [(&str, u64); 5] = [
+    ("square", 0xdcca_a9bb_9e1b_c4b9),
+    ("disc", 0x5bc5_a57f_ffbc_49cb),
+    ("lines", 0x33af_2407_b6a4_a285),
+    ("checkerboard", 0xe16e_dd0f_391c_4bb6),
+    ("step_on_ramp", 0x4edb_3d53_3a45_4127),
+];
+
+/// The synthetic test patterns, by name
+pub fn patterns() -> Vec<(&'static str, Image)> {
+    let binary = |on: bool| Rgba::gray(if on { 1.0 } else { 0.0 });
+    let centre = (SIZE as f64 - 1.0) / 2.0;
+    vec![
+        ("square", Image::from_fn(SIZE, SIZE, |x, y| binary(
+            (12..36).contains(&x) && (12..36).contains(&y)))),
+        ("disc", Image::from_fn(SIZE, SIZE, |x, y| binary(
+            (x as f64 - centre).hypot(y as f64 - centre) < 15.0))),
+        ("lines", Image::from_fn(SIZE, SIZE, |x, y| binary(x % 12 == 6 || y % 16 == 8))),
+        ("checkerboard", Image::from_fn(SIZE, SIZE, |x, y| binary((x / 12 + y / 12) % 2 == 1))),
+        ("step_on_ramp", Image::from_fn(SIZE, SIZE, |x, y| Rgba::gray(
+            x as f64 / SIZE as f64 * 0.5 + if y >= SIZE / 2 { 0.5 } else { 0.0 }))),
+    ]
+}
+
+pub fn pattern(name: &str) -> Option<Image> {
+    patterns().into_iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, image)| image)
+}
+
+/// The reference output for a pattern, from the CPU pipeline
+pub fn expected_image(name: &str) -> Option<Image> {
+    pattern(name).map(|image| CpuPipeline::default()
+        .canny(CANNY_LOW, CANNY_HIGH)
+        .apply(&image))
+}
+
+pub fn expected_hash(name: &str) -> Option<u64> {
+    EXPECTED.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, hash)| *hash)
+}
+
+/// FNV-1a hash of the dimensions and of which pixels are edges, so that it
+/// stays the same across platforms and compiler versions
+pub fn edge_hash(edges: &Image) -> u64 {
+    const PRIME: u64 = 0x100_0000_01b3;
+    let bytes = [edges.width() as u64, edges.height() as u64]
+        .into_iter()
+        .flat_map(u64::to_le_bytes)
+        .chain((0..edges.height())
+            .flat_map(|y| (0..edges.width())
+                .map(move |x| (x, y)))
+            .map(|(x, y)| (edges[(x, y)].intensity() > 0.5) as u8));
+    bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+/// Compares another backend's output for a pattern with the reference. At
+/// most `tolerance` of the pixels may disagree on being an edge
+pub fn verify(name: &str, output: &Image, tolerance: f64) -> Result<(), String> {
+    let expected = expected_image(name)
+        .ok_or_else(|| format!("Unknown pattern '{name}'"))?;
+    if (output.width(), output.height()) != (expected.width(), expected.height()) {
+        return Err(format!("Expected a {}x{} image for '{name}', got {}x{}",
+                           expected.width(), expected.height(), output.width(), output.height()));
+    }
+    let differing = (0..expected.width())
+        .flat_map(|x| (0..expected.height())
+            .map(move |y| (x, y)))
+        .filter(|&p| (expected[p].intensity() > 0.5) != (output[p].intensity() > 0.5))
+        .count();
+    let fraction = differing as f64 / (expected.width() * expected.height()) as f64;
+    if fraction > tolerance {
+        return Err(format!("{differing} pixels of '{name}' differ from the reference"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edge_hash, expected_image, EXPECTED};
+
+    #[test]
+    fn cpu_matches_golden_hashes() {
+        for (name, hash) in EXPECTED {
+            let image = expected_image(name).unwrap();
+            assert_eq!(edge_hash(&image), hash, "{name}");
+        }
+    }
+}
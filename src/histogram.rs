@@ -0,0 +1,128 @@
+/// Counts of values in [0, 1], split into equally sized bins.
+/// Values outside of the range land in the first or last bin
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    bins: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new(bins: usize) -> Histogram {
+        assert!(bins > 0, "A histogram needs at least one bin");
+        Histogram {
+            bins: vec![0; bins]
+        }
+    }
+
+    pub fn from_values(bins: usize, values: impl IntoIterator<Item = f64>) -> Histogram {
+        let mut histogram = Histogram::new(bins);
+        values.into_iter()
+            .for_each(|value| histogram.add(value));
+        histogram
+    }
+
+    pub fn add(&mut self, value: f64) {
+        let bin = self.bin(value);
+        self.bins[bin] += 1;
+    }
+
+    pub fn bin(&self, value: f64) -> usize {
+        ((value.clamp(0.0, 1.0) * self.bins.len() as f64) as usize).min(self.bins.len() - 1)
+    }
+
+    /// The value at the centre of a bin
+    pub fn value(&self, bin: usize) -> f64 {
+        (bin as f64 + 0.5) / self.bins.len() as f64
+    }
+
+    pub fn bins(&self) -> &[usize] {
+        &self.bins
+    }
+
+    pub fn total(&self) -> usize {
+        self.bins.iter().sum()
+    }
+
+    /// The smallest bin centre with at least `fraction` of the values at or below it
+    pub fn percentile(&self, fraction: f64) -> f64 {
+        let target = fraction.clamp(0.0, 1.0) * self.total() as f64;
+        let mut seen = 0;
+        for (n, count) in self.bins.iter().enumerate() {
+            seen += count;
+            if seen as f64 >= target && seen > 0 {
+                return self.value(n);
+            }
+        }
+        self.value(self.bins.len() - 1)
+    }
+
+    /// Fraction of the values in each bin or below it
+    pub fn cdf(&self) -> Vec<f64> {
+        let total = self.total().max(1) as f64;
+        self.bins.iter()
+            .scan(0, |seen, count| {
+                *seen += count;
+                Some(*seen as f64 / total)
+            })
+            .collect()
+    }
+
+    pub fn median(&self) -> f64 {
+        self.percentile(0.5)
+    }
+
+    /// Otsu's threshold: the value that maximizes the between-class
+    /// variance of the values below and above it
+    pub fn otsu(&self) -> f64 {
+        let total = self.total() as f64;
+        let sum = self.bins.iter()
+            .enumerate()
+            .map(|(n, count)| n as f64 * *count as f64)
+            .sum::<f64>();
+
+        let (mut weight_below, mut sum_below) = (0.0, 0.0);
+        let (mut best, mut best_variance) = (0, f64::MIN);
+        for (n, count) in self.bins.iter().enumerate() {
+            weight_below += *count as f64;
+            sum_below += n as f64 * *count as f64;
+            let weight_above = total - weight_below;
+            if weight_below == 0.0 || weight_above == 0.0 {
+                continue;
+            }
+            let mean_below = sum_below / weight_below;
+            let mean_above = (sum - sum_below) / weight_above;
+            let variance = weight_below * weight_above * (mean_below - mean_above).powi(2);
+            if variance > best_variance {
+                best = n;
+                best_variance = variance;
+            }
+        }
+        (best + 1) as f64 / self.bins.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+
+    #[test]
+    fn otsu_splits_two_modes() {
+        let dark = (0..100).map(|n| 0.15 + 0.1 * n as f64 / 100.0).collect::<Vec<_>>();
+        let bright = (0..50).map(|n| 0.75 + 0.1 * n as f64 / 50.0).collect::<Vec<_>>();
+        let histogram = Histogram::from_values(64, dark.iter().chain(&bright).copied());
+        let threshold = histogram.otsu();
+        assert!(dark.iter().all(|v| *v < threshold) && bright.iter().all(|v| *v >= threshold), "{threshold}");
+    }
+
+    #[test]
+    fn percentiles_follow_the_cdf() {
+        let histogram = Histogram::from_values(10, (0..10).map(|n| n as f64 / 10.0 + 0.05));
+        assert_eq!(histogram.total(), 10);
+        assert!((histogram.percentile(0.5) - 0.45).abs() < 1e-12);
+        assert!((histogram.median() - 0.45).abs() < 1e-12);
+        assert!((histogram.percentile(1.0) - 0.95).abs() < 1e-12);
+        let cdf = histogram.cdf();
+        assert!((cdf[4] - 0.5).abs() < 1e-12);
+        assert_eq!(cdf[9], 1.0);
+        assert_eq!(Histogram::new(4).cdf(), vec![0.0; 4]);
+    }
+}
@@ -0,0 +1,44 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use image::{ImageDecoder, ImageFormat, ImageResult, RgbaImage};
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use qcms::{DataType, Intent, Profile, Transform};
+use crate::cpu::Image;
+
+/// The ICC profile embedded in a JPEG or PNG file, if it has one
+pub fn read_icc_profile(path: impl AsRef<Path>) -> ImageResult<Option<Vec<u8>>> {
+    let reader = || File::open(&path).map(BufReader::new);
+    let format = image::io::Reader::open(&path)?
+        .with_guessed_format()?
+        .format();
+    Ok(match format {
+        Some(ImageFormat::Jpeg) => JpegDecoder::new(reader()?)?.icc_profile(),
+        Some(ImageFormat::Png) => PngDecoder::new(reader()?)?.icc_profile(),
+        _ => None,
+    })
+}
+
+/// Converts pixels described by `profile` to sRGB in place. Returns whether
+/// the profile could be used; the image is left untouched otherwise
+pub fn convert_to_srgb(image: &mut RgbaImage, profile: &[u8]) -> bool {
+    let transform = Profile::new_from_slice(profile, false)
+        .and_then(|input| Transform::new(&input, &Profile::new_sRGB(), DataType::RGBA8, Intent::Perceptual));
+    match transform {
+        Some(transform) => {
+            transform.apply(image);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Loads an image, converting it to sRGB if it has an embedded ICC profile
+pub fn open_srgb(path: impl AsRef<Path>) -> ImageResult<Image> {
+    let mut image = image::open(&path)?.into_rgba8();
+    if let Some(profile) = read_icc_profile(&path)? {
+        convert_to_srgb(&mut image, &profile);
+    }
+    Ok(image.into())
+}
@@ -0,0 +1,93 @@
+use crate::cpu::Image;
+use crate::rgba::Rgba;
+
+/// Summed-area table of an image, per channel. Entry `(x, y)` holds the sum
+/// of all pixels above and to the left of it, exclusively
+pub struct IntegralImage {
+    sums: Vec<Vec<[f64; 4]>>,
+}
+
+impl IntegralImage {
+    pub fn new(image: &Image) -> IntegralImage {
+        IntegralImage::mapped(image, |pixel| pixel)
+    }
+
+    /// Sums of the squared channels, for window variances along with `new`
+    pub fn squares(image: &Image) -> IntegralImage {
+        IntegralImage::mapped(image, |pixel| pixel * pixel)
+    }
+
+    fn mapped(image: &Image, f: impl Fn(Rgba) -> Rgba) -> IntegralImage {
+        let (width, height) = (image.width(), image.height());
+        let mut sums = vec![vec![[0.0; 4]; height + 1]; width + 1];
+        for x in 0..width {
+            for y in 0..height {
+                let pixel: [f64; 4] = f(image[(x, y)]).into();
+                for c in 0..4 {
+                    sums[x + 1][y + 1][c] = pixel[c] + sums[x][y + 1][c] + sums[x + 1][y][c] - sums[x][y][c];
+                }
+            }
+        }
+        IntegralImage { sums }
+    }
+
+    pub fn width(&self) -> usize {
+        self.sums.len() - 1
+    }
+
+    pub fn height(&self) -> usize {
+        self.sums.first()
+            .map(|v| v.len() - 1)
+            .unwrap_or(0)
+    }
+
+    /// Sum of the pixels in `[x0, x1) x [y0, y1)`
+    pub fn sum(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> Rgba {
+        (0..4)
+            .map(|c| self.sums[x1][y1][c] - self.sums[x0][y1][c] - self.sums[x1][y0][c] + self.sums[x0][y0][c])
+            .collect()
+    }
+
+    /// Mean of the `(2 * radius + 1)` square window around `(x, y)`,
+    /// clipped to the image
+    pub fn mean(&self, x: usize, y: usize, radius: usize) -> Rgba {
+        let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let x1 = (x + radius + 1).min(self.width());
+        let y1 = (y + radius + 1).min(self.height());
+        self.sum(x0, y0, x1, y1) / ((x1 - x0) * (y1 - y0)) as f64
+    }
+
+    /// Variance of the same window as `mean`, given the integral of the
+    /// squares of the same image
+    pub fn variance(&self, squares: &IntegralImage, x: usize, y: usize, radius: usize) -> Rgba {
+        let mean = self.mean(x, y, radius);
+        (squares.mean(x, y, radius) - mean * mean)
+            .map(|v| v.max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::IntegralImage;
+
+    #[test]
+    fn window_sums_match_brute_force() {
+        let image = Image::from_fn(5, 4, |x, y| Rgba::gray((x * 4 + y) as f64 / 20.0));
+        let (sums, squares) = (IntegralImage::new(&image), IntegralImage::squares(&image));
+        assert_eq!((sums.width(), sums.height()), (5, 4));
+        let expected = (1..4)
+            .flat_map(|x| (0..2)
+                .map(move |y| (x * 4 + y) as f64 / 20.0))
+            .sum::<f64>();
+        assert!((sums.sum(1, 0, 4, 2).red() - expected).abs() < 1e-12);
+        // The window around a corner is clipped to the 2x2 pixels inside
+        let corner = [0.0, 1.0, 4.0, 5.0].map(|v| v / 20.0);
+        let mean = corner.iter().sum::<f64>() / 4.0;
+        let variance = corner.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / 4.0;
+        assert!((sums.mean(0, 0, 1).green() - mean).abs() < 1e-12);
+        assert!((sums.variance(&squares, 0, 0, 1).blue() - variance).abs() < 1e-12);
+    }
+}
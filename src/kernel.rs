@@ -0,0 +1,106 @@
+use std::path::Path;
+use crate::Filter;
+
+/// Rows of convolution weights, as loaded from files written by other tools
+#[derive(Clone, Debug, PartialEq)]
+pub struct Kernel {
+    pub rows: Vec<Vec<f64>>,
+}
+
+impl Kernel {
+    /// Rows on separate lines, with weights separated by commas or
+    /// whitespace, as written by MATLAB's `writematrix` or NumPy's `savetxt`.
+    /// Blank lines and lines starting with `#` are skipped
+    pub fn from_csv(text: &str) -> Result<Kernel, String> {
+        let rows = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|weight| !weight.is_empty())
+                .map(parse_weight)
+                .collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+        Kernel::new(rows)
+    }
+
+    /// An array of rows of numbers, as written by `jsonencode` in MATLAB or
+    /// `json.dump(kernel.tolist())` in Python. A flat array is a single row
+    pub fn from_json(text: &str) -> Result<Kernel, String> {
+        let inner = text.trim()
+            .strip_prefix('[')
+            .and_then(|t| t.strip_suffix(']'))
+            .ok_or("Expected a JSON array of rows")?;
+        let numbers = |row: &str| row.split(',')
+            .map(str::trim)
+            .filter(|weight| !weight.is_empty())
+            .map(parse_weight)
+            .collect::<Result<Vec<_>, _>>();
+        if !inner.contains('[') {
+            return Kernel::new(vec![numbers(inner)?]);
+        }
+        let rows = inner.split(']')
+            .map(|row| row.trim().trim_start_matches(',').trim())
+            .filter(|row| !row.is_empty())
+            .map(|row| row.strip_prefix('[')
+                .ok_or_else(|| format!("Expected a row, found '{row}'"))
+                .and_then(numbers))
+            .collect::<Result<Vec<_>, _>>()?;
+        Kernel::new(rows)
+    }
+
+    /// Reads a kernel from a `.json` file, or from CSV otherwise
+    pub fn load(path: impl AsRef<Path>) -> Result<Kernel, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read kernel {}: {err}", path.display()))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Kernel::from_json(&text),
+            _ => Kernel::from_csv(&text),
+        }
+    }
+
+    fn new(rows: Vec<Vec<f64>>) -> Result<Kernel, String> {
+        let width = rows.first().map_or(0, Vec::len);
+        if width == 0 {
+            return Err("The kernel is empty".to_string());
+        }
+        if let Some(n) = rows.iter().position(|row| row.len() != width) {
+            return Err(format!("Row {} has {} weights, expected {width}", n + 1, rows[n].len()));
+        }
+        Ok(Kernel { rows })
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+fn parse_weight(weight: &str) -> Result<f64, String> {
+    weight.parse()
+        .map_err(|_| format!("Invalid weight '{weight}'"))
+}
+
+impl<Image> From<Kernel> for Filter<Image> {
+    fn from(kernel: Kernel) -> Self {
+        Filter::Custom(kernel.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kernel;
+
+    #[test]
+    fn csv_and_json_give_the_same_kernel() {
+        let csv = Kernel::from_csv("# sharpen\n0, -1, 0\n-1 5 -1\n\n0,-1,0\n").unwrap();
+        let json = Kernel::from_json("[[0, -1, 0], [-1, 5, -1], [0, -1, 0]]").unwrap();
+        assert_eq!(csv, json);
+        assert_eq!((csv.width(), csv.height()), (3, 3));
+        assert!(Kernel::from_json("[[1, 2], [3]]").is_err());
+        assert_eq!(Kernel::from_json("[1, 2, 1]").unwrap().rows, vec![vec![1.0, 2.0, 1.0]]);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+use crate::cpu::Image;
+use crate::rgba::Rgba;
+
+/// Per-pixel region labels, indexed as `[x][y]` like the images.
+/// Label `0` is the background
+#[derive(Clone)]
+pub struct LabelImage(Vec<Vec<usize>>);
+
+impl std::ops::Index<(usize, usize)> for LabelImage {
+    type Output = usize;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Self::Output {
+        &self.0[x][y]
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for LabelImage {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
+        &mut self.0[x][y]
+    }
+}
+
+impl LabelImage {
+    pub fn new(width: usize, height: usize) -> LabelImage {
+        LabelImage(vec![vec![0; height]; width])
+    }
+
+    pub fn width(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.0.first()
+            .map(|v| v.len())
+            .unwrap_or(0)
+    }
+
+    /// The largest label in use
+    pub fn count(&self) -> usize {
+        self.0.iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Labels the 8-connected regions of pixels whose intensity is above `threshold`
+    pub fn connected_components(image: &Image, threshold: f64) -> LabelImage {
+        let (width, height) = (image.width(), image.height());
+        let foreground = |x: usize, y: usize| image[(x, y)].intensity() > threshold;
+        let mut labels = LabelImage::new(width, height);
+        let mut next = 0;
+        for x in 0..width {
+            for y in 0..height {
+                if labels[(x, y)] != 0 || !foreground(x, y) {
+                    continue;
+                }
+                next += 1;
+                labels[(x, y)] = next;
+                let mut queue = VecDeque::from([(x, y)]);
+                while let Some((x, y)) = queue.pop_front() {
+                    let neighbours = (x.saturating_sub(1)..=(x + 1).min(width - 1))
+                        .flat_map(|nx| (y.saturating_sub(1)..=(y + 1).min(height - 1))
+                            .map(move |ny| (nx, ny)));
+                    for (nx, ny) in neighbours {
+                        if labels[(nx, ny)] == 0 && foreground(nx, ny) {
+                            labels[(nx, ny)] = next;
+                            queue.push_back((nx, ny));
+                        }
+                    }
+                }
+            }
+        }
+        labels
+    }
+
+    /// Bounding boxes as `(min_x, min_y, max_x, max_y)`, inclusive, indexed by `label - 1`
+    pub fn bounding_boxes(&self) -> Vec<Option<(usize, usize, usize, usize)>> {
+        let mut boxes = vec![None; self.count()];
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                let label = self[(x, y)];
+                if label == 0 {
+                    continue;
+                }
+                let bbox = boxes[label - 1].get_or_insert((x, y, x, y));
+                *bbox = (bbox.0.min(x), bbox.1.min(y), bbox.2.max(x), bbox.3.max(y));
+            }
+        }
+        boxes
+    }
+}
+
+/// Measurements of a labelled region
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RegionProps {
+    pub label: usize,
+    /// Number of pixels
+    pub area: usize,
+    /// Mean pixel position as `(x, y)`
+    pub centroid: (f64, f64),
+    /// Number of pixel edges between the region and anything else
+    pub perimeter: usize,
+    /// Of the ellipse with the same second moments, `0` for a circle and
+    /// approaching `1` for a line
+    pub eccentricity: f64,
+    /// Angle of that ellipse's major axis from the x axis, in radians
+    pub orientation: f64,
+}
+
+impl LabelImage {
+    /// Measures every label in use, in increasing order of label
+    pub fn regionprops(&self) -> Vec<RegionProps> {
+        let count = self.count();
+        let mut area = vec![0usize; count];
+        let mut sums = vec![(0.0, 0.0); count];
+        let mut perimeter = vec![0usize; count];
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                let label = self[(x, y)];
+                if label == 0 {
+                    continue;
+                }
+                area[label - 1] += 1;
+                sums[label - 1].0 += x as f64;
+                sums[label - 1].1 += y as f64;
+                perimeter[label - 1] += [(0, -1), (1, 0), (0, 1), (-1, 0)].into_iter()
+                    .map(|(i, j)| (x as i64 + i, y as i64 + j))
+                    .filter(|&(nx, ny)| nx < 0 || ny < 0
+                        || nx >= self.width() as i64 || ny >= self.height() as i64
+                        || self[(nx as usize, ny as usize)] != label)
+                    .count();
+            }
+        }
+        let centroids = area.iter()
+            .zip(&sums)
+            .map(|(area, (sx, sy))| (sx / *area.max(&1) as f64, sy / *area.max(&1) as f64))
+            .collect::<Vec<_>>();
+
+        // Central second moments
+        let mut moments = vec![(0.0, 0.0, 0.0); count];
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                let label = self[(x, y)];
+                if label == 0 {
+                    continue;
+                }
+                let (cx, cy) = centroids[label - 1];
+                let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+                let m = &mut moments[label - 1];
+                *m = (m.0 + dx * dx, m.1 + dx * dy, m.2 + dy * dy);
+            }
+        }
+
+        (0..count)
+            .filter(|n| area[*n] > 0)
+            .map(|n| {
+                let a = area[n] as f64;
+                let (mu20, mu11, mu02) = (moments[n].0 / a, moments[n].1 / a, moments[n].2 / a);
+                let spread = (((mu20 - mu02) / 2.0).powi(2) + mu11 * mu11).sqrt();
+                let (major, minor) = ((mu20 + mu02) / 2.0 + spread, (mu20 + mu02) / 2.0 - spread);
+                RegionProps {
+                    label: n + 1,
+                    area: area[n],
+                    centroid: centroids[n],
+                    perimeter: perimeter[n],
+                    eccentricity: if major > 0.0 { (1.0 - minor / major).max(0.0).sqrt() } else { 0.0 },
+                    orientation: 0.5 * (2.0 * mu11).atan2(mu20 - mu02),
+                }
+            })
+            .collect()
+    }
+}
+
+/// `n` well separated colours, spreading hues by the golden angle
+pub fn generate_palette(n: usize) -> Vec<Rgba> {
+    (0..n)
+        .map(|i| Rgba::from_hsv((i as f64 * 0.618_033_988_75).fract() * 360.0, 0.75, 0.95))
+        .collect()
+}
+
+/// Paints the background black and every label `n` with `palette[(n - 1) % palette.len()]`
+pub fn label_to_color(labels: &LabelImage, palette: &[Rgba]) -> Image {
+    let generated;
+    let palette = if palette.is_empty() {
+        generated = generate_palette(labels.count().max(1));
+        &generated[..]
+    } else {
+        palette
+    };
+    Image::empty(labels.width(), labels.height())
+        .similar(|x, y| match labels[(x, y)] {
+            0 => Rgba::BLACK,
+            n => palette[(n - 1) % palette.len()],
+        })
+}
+
+/// Outlines the bounding box of every labelled region on top of `image`
+pub fn draw_component_bboxes(image: &Image, labels: &LabelImage, colour: Rgba) -> Image {
+    let boxes = labels.bounding_boxes()
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    image.similar(|x, y| {
+        let on_outline = boxes.iter()
+            .any(|&(x0, y0, x1, y1)| {
+                let within_x = (x0..=x1).contains(&x);
+                let within_y = (y0..=y1).contains(&y);
+                (within_x && (y == y0 || y == y1)) || (within_y && (x == x0 || x == x1))
+            });
+        if on_outline {
+            colour
+        } else {
+            image[(x, y)]
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_4;
+    use crate::cpu::Image;
+    use super::LabelImage;
+
+    #[test]
+    fn measures_a_block_and_a_diagonal() {
+        // A 3x2 block and two diagonally touching pixels
+        let image = Image::from_weights(&(0..6)
+            .map(|y| (0..8)
+                .map(|x| match (x, y) {
+                    (1..=3, 1..=2) | (6, 4) | (7, 5) => 1.0,
+                    _ => 0.0,
+                })
+                .collect())
+            .collect::<Vec<_>>());
+        let labels = LabelImage::connected_components(&image, 0.5);
+        assert_eq!(labels.count(), 2);
+        assert_eq!(labels.bounding_boxes(), vec![Some((1, 1, 3, 2)), Some((6, 4, 7, 5))]);
+
+        let props = labels.regionprops();
+        assert_eq!(props.len(), 2);
+        let (block, diagonal) = (props[0], props[1]);
+        assert_eq!((block.label, block.area, block.perimeter), (1, 6, 10));
+        assert_eq!(block.centroid, (2.0, 1.5));
+        assert!((block.eccentricity - 0.625f64.sqrt()).abs() < 1e-12);
+        assert!(block.orientation.abs() < 1e-12);
+
+        assert_eq!((diagonal.label, diagonal.area, diagonal.perimeter), (2, 2, 8));
+        assert_eq!(diagonal.centroid, (6.5, 4.5));
+        assert!((diagonal.eccentricity - 1.0).abs() < 1e-12);
+        assert!((diagonal.orientation - FRAC_PI_4).abs() < 1e-12);
+    }
+}
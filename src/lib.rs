@@ -2,6 +2,36 @@
 pub mod pipeline;
 pub mod cpu;
 pub mod rgba;
+pub mod fft;
+pub mod labels;
+pub mod integral;
+pub mod histogram;
+pub mod ransac;
+pub mod cached;
+pub mod chamfer;
+pub mod export;
+pub mod golden;
+pub mod mask;
+pub mod raw;
+pub mod contours;
+pub mod steerable;
+pub mod compare;
+pub mod backend;
+pub mod phash;
+pub mod texture;
+pub mod lsd;
+pub mod palette;
+pub mod background;
+pub mod kernel;
+pub mod wavelet;
+pub mod metrics;
+pub mod font;
+#[cfg(feature = "io")]
+pub mod exif;
+#[cfg(feature = "io")]
+pub mod pnm;
+#[cfg(feature = "icc")]
+pub mod icc;
 
 extern crate lazy_static;
 extern crate rand;
@@ -9,9 +39,324 @@ extern crate num_traits;
 extern crate probability;
 extern crate core;
 
+use rgba::Rgba;
+
 pub enum Filter<Image> {
     Convoluted(Image),
+    /// Rows of weights, centred on the pixel being computed like `Convoluted`
+    Custom(Vec<Vec<f64>>),
+    /// Per-channel median of a `size`x`size` window
     Median(usize),
+    /// Sum of the per-channel minimum and maximum of a `size`x`size` window
+    Midpoint(usize),
+}
+
+/// How pixels outside of the image are sampled
+#[derive(Copy, Clone, Debug, Default)]
+pub enum BorderMode {
+    /// Repeat the nearest edge pixel
+    #[default]
+    Replicate,
+    /// Mirror the image around its edge pixels, which are not repeated
+    Reflect,
+    /// Continue from the opposite edge, as if the image were tiled
+    Wrap,
+    Constant(Rgba),
+}
+
+/// Size of the output of a windowed filter of `k` by `k` on an `n` by `n` image
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputSize {
+    /// `n`, sampling outside of the image near the edges
+    #[default]
+    Same,
+    /// `n - k + 1`, only where the window fits in the image
+    Valid,
+    /// `n + k - 1`, wherever the window overlaps the image
+    Full,
+}
+
+/// Which kernel element lies on top of the pixel being computed
+#[derive(Copy, Clone, Debug)]
+pub enum Anchor {
+    /// The middle element, or the one right after the middle for even sizes
+    Center,
+    At(usize, usize),
+}
+
+impl Anchor {
+    pub fn resolve(&self, width: usize, height: usize) -> (i64, i64) {
+        match *self {
+            Anchor::Center => ((width / 2) as i64, (height / 2) as i64),
+            Anchor::At(x, y) => (x as i64, y as i64),
+        }
+    }
+}
+
+/// How the weighted taps of a convolution are combined
+#[derive(Copy, Clone, Debug)]
+pub enum ReduceOp {
+    Sum,
+    Min,
+    Max,
+}
+
+pub enum BayerSize {
+    Two,
+    Four,
+    Eight,
+}
+
+impl BayerSize {
+    pub fn side(&self) -> usize {
+        match self {
+            BayerSize::Two => 2,
+            BayerSize::Four => 4,
+            BayerSize::Eight => 8,
+        }
+    }
+
+    /// The threshold map, indexed as `[x][y]` like the images
+    pub fn matrix(&self) -> Vec<Vec<usize>> {
+        let mut matrix = vec![vec![0]];
+        while matrix.len() < self.side() {
+            let n = matrix.len();
+            matrix = (0..2 * n)
+                .map(|x| (0..2 * n)
+                    .map(|y| 4 * matrix[x % n][y % n] + match (x / n, y / n) {
+                        (0, 0) => 0,
+                        (1, 1) => 1,
+                        (1, 0) => 2,
+                        _ => 3,
+                    })
+                    .collect())
+                .collect();
+        }
+        matrix
+    }
+}
+
+pub enum DitherMethod {
+    FloydSteinberg,
+    Ordered(BayerSize),
+}
+
+pub enum ColorVisionDeficiency {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ColorVisionDeficiency {
+    /// Machado et al. (2009) simulation matrices at full severity, in linear RGB
+    pub fn matrix(&self) -> [[f64; 3]; 3] {
+        match self {
+            ColorVisionDeficiency::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorVisionDeficiency::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVisionDeficiency::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+pub enum GradientNorm {
+    /// `|dx| + |dy|`
+    L1,
+    /// `sqrt(dx^2 + dy^2)`
+    L2,
+}
+
+/// Shape of the gain of a frequency domain filter around its cutoff
+#[derive(Copy, Clone, Debug)]
+pub enum FrequencyResponse {
+    /// Passes everything up to the cutoff and nothing above, at the cost of ringing
+    Ideal,
+    /// Rolls off more sharply with higher orders, approaching `Ideal`
+    Butterworth(u32),
+    /// Smooth, without ringing, with the cutoff at one standard deviation
+    Gaussian,
+}
+
+impl FrequencyResponse {
+    /// Low-pass gain at `frequency`, in cycles per pixel like `cutoff`
+    pub fn lowpass(&self, frequency: f64, cutoff: f64) -> f64 {
+        match self {
+            FrequencyResponse::Ideal => if frequency <= cutoff { 1.0 } else { 0.0 },
+            FrequencyResponse::Butterworth(order) => 1.0 / (1.0 + (frequency / cutoff).powi(2 * *order as i32)),
+            FrequencyResponse::Gaussian => (-frequency * frequency / (2.0 * cutoff * cutoff)).exp(),
+        }
+    }
+}
+
+/// How wavelet coefficients are thresholded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shrinkage {
+    /// Zero the coefficients below the threshold, keep the rest
+    Hard,
+    /// Also move the rest towards zero by the threshold
+    Soft,
+}
+
+/// Which channels of an image noise is added to
+#[derive(Copy, Clone, Debug)]
+pub enum NoiseChannels {
+    All,
+    /// The same noise on every colour channel, leaving hue untouched
+    Luminance,
+    Only {
+        red: bool,
+        green: bool,
+        blue: bool,
+    },
+}
+
+pub enum Stacking {
+    Mean,
+    Median,
+}
+
+/// How the stored pixels relate to the upright image, as recorded by the
+/// EXIF orientation tag. Rotations are clockwise
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Orientation {
+    #[default]
+    Normal,
+    FlipHorizontal,
+    Rotate180,
+    FlipVertical,
+    /// Mirrored along the main diagonal
+    Transpose,
+    Rotate90,
+    /// Mirrored along the anti-diagonal
+    Transverse,
+    Rotate270,
+}
+
+impl Orientation {
+    /// From the value of the EXIF orientation tag, `1` to `8`
+    pub fn from_exif(value: u16) -> Option<Orientation> {
+        Some(match value {
+            1 => Orientation::Normal,
+            2 => Orientation::FlipHorizontal,
+            3 => Orientation::Rotate180,
+            4 => Orientation::FlipVertical,
+            5 => Orientation::Transpose,
+            6 => Orientation::Rotate90,
+            7 => Orientation::Transverse,
+            8 => Orientation::Rotate270,
+            _ => return None,
+        })
+    }
+}
+
+/// Shape of the neighbourhood used by morphological operations, centred
+/// like `Anchor::Center`
+#[derive(Copy, Clone, Debug)]
+pub enum StructuringElement {
+    Rect(usize, usize),
+    /// The middle row and column of a rectangle
+    Cross(usize, usize),
+    /// The ellipse inscribed in a rectangle
+    Ellipse(usize, usize),
+}
+
+impl StructuringElement {
+    pub fn size(&self) -> (usize, usize) {
+        match *self {
+            StructuringElement::Rect(w, h)
+            | StructuringElement::Cross(w, h)
+            | StructuringElement::Ellipse(w, h) => (w, h),
+        }
+    }
+
+    /// Offsets of the element's pixels from its centre
+    pub fn offsets(&self) -> Vec<(i64, i64)> {
+        let (width, height) = self.size();
+        let (cx, cy) = Anchor::Center.resolve(width, height);
+        (0..width as i64)
+            .flat_map(|x| (0..height as i64)
+                .map(move |y| (x, y)))
+            .filter(|&(x, y)| match *self {
+                StructuringElement::Rect(..) => true,
+                StructuringElement::Cross(..) => x == cx || y == cy,
+                StructuringElement::Ellipse(..) => {
+                    let dx = (x as f64 - (width - 1) as f64 / 2.0) / (width as f64 / 2.0);
+                    let dy = (y as f64 - (height - 1) as f64 / 2.0) / (height as f64 / 2.0);
+                    dx * dx + dy * dy <= 1.0
+                }
+            })
+            .map(|(x, y)| (x - cx, y - cy))
+            .collect()
+    }
+}
+
+/// Byte layout of 8 bit pixels in an external buffer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8,
+    Bgra8,
+    Argb8,
+    Rgb8,
+    Gray8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 | PixelFormat::Argb8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// How an image is sampled between pixel centres
+#[derive(Copy, Clone, Debug)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+    /// Catmull-Rom over the 4x4 nearest pixels, sharper than `Bilinear` but
+    /// overshooting slightly around edges
+    Bicubic,
+}
+
+/// Geometric correction of a colour channel relative to green.
+/// A pixel at normalized radius `r` from the centre is sampled from
+/// `centre + (p - centre) * (scale + k1 r^2 + k2 r^4) + shift`
+#[derive(Copy, Clone, Debug)]
+pub struct ChannelCorrection {
+    pub scale: f64,
+    pub shift: (f64, f64),
+    pub k1: f64,
+    pub k2: f64,
+}
+
+impl Default for ChannelCorrection {
+    fn default() -> Self {
+        ChannelCorrection {
+            scale: 1.0,
+            shift: (0.0, 0.0),
+            k1: 0.0,
+            k2: 0.0,
+        }
+    }
 }
 
 trait Map2D {
@@ -0,0 +1,212 @@
+use std::f64::consts::{FRAC_PI_8, PI};
+use crate::cpu::{gaussian_derivative_weights, Image};
+use crate::ransac::Point;
+use crate::{Anchor, BorderMode, ReduceOp};
+
+/// Angle tolerance for a pixel to support a line
+const TOLERANCE: f64 = FRAC_PI_8;
+/// Of the blur applied before taking gradients
+const SIGMA: f64 = 0.75;
+/// Gradients below this are too sensitive to quantization noise
+const MIN_GRADIENT: f64 = 2.0 / 255.0 / 0.382_683_432_365_089_8;
+
+/// A straight segment found by `lsd`
+#[derive(Copy, Clone, Debug)]
+pub struct LineSegment {
+    pub start: Point,
+    pub end: Point,
+    pub width: f64,
+    /// Minus the base ten logarithm of the expected number of segments at
+    /// least this well aligned in noise. Above zero for every result
+    pub significance: f64,
+}
+
+impl LineSegment {
+    pub fn length(&self) -> f64 {
+        (self.end.0 - self.start.0).hypot(self.end.1 - self.start.1)
+    }
+}
+
+/// Difference of two angles, in [0, pi]
+fn angle_diff(a: f64, b: f64) -> f64 {
+    let d = (a - b).rem_euclid(2.0 * PI);
+    d.min(2.0 * PI - d)
+}
+
+/// Natural logarithm of the probability of at least `k` successes in `n`
+/// trials of probability `p`
+fn log_binomial_tail(n: usize, k: usize, p: f64, log_factorial: &[f64]) -> f64 {
+    let term = |i: usize| log_factorial[n] - log_factorial[i] - log_factorial[n - i]
+        + i as f64 * p.ln() + (n - i) as f64 * (1.0 - p).ln();
+    let terms = (k..=n).map(term).collect::<Vec<_>>();
+    let max = terms.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    max + terms.iter().map(|t| (t - max).exp()).sum::<f64>().ln()
+}
+
+/// Line segment detector after von Gioi et al.: grows regions of pixels
+/// whose level lines share an orientation, fits a rectangle to each and
+/// keeps the ones too well aligned to be noise. Works on the intensity,
+/// without any prior edge detection
+pub fn lsd(image: &Image) -> Vec<LineSegment> {
+    let (width, height) = (image.width(), image.height());
+    if width < 2 || height < 2 {
+        return vec![];
+    }
+    // A little blur keeps the staircase of aliased slanted edges from
+    // splitting them into differently oriented pieces
+    let kernel = Image::from_weights(&gaussian_derivative_weights(5, SIGMA, 0, 0));
+    let smooth = image.convolve(&kernel, Anchor::Center, BorderMode::Replicate, ReduceOp::Sum);
+    let at = |x: usize, y: usize| smooth[(x, y)].intensity();
+
+    // 2x2 gradients, sampled at the pixel corners
+    let mut magnitude = vec![vec![0.0; height]; width];
+    let mut angle = vec![vec![0.0; height]; width];
+    for x in 0..width - 1 {
+        for y in 0..height - 1 {
+            let gx = (at(x + 1, y) + at(x + 1, y + 1) - at(x, y) - at(x, y + 1)) / 2.0;
+            let gy = (at(x, y + 1) + at(x + 1, y + 1) - at(x, y) - at(x + 1, y)) / 2.0;
+            magnitude[x][y] = gx.hypot(gy);
+            // Level lines run across the gradient
+            angle[x][y] = gx.atan2(-gy);
+        }
+    }
+
+    let mut seeds = (0..width - 1)
+        .flat_map(|x| (0..height - 1).map(move |y| (x, y)))
+        .filter(|&(x, y)| magnitude[x][y] > MIN_GRADIENT)
+        .collect::<Vec<_>>();
+    seeds.sort_by(|a, b| magnitude[b.0][b.1].total_cmp(&magnitude[a.0][a.1]));
+
+    let log_factorial = (0..=width * height)
+        .scan(0.0, |sum, n: usize| {
+            *sum += (n.max(1) as f64).ln();
+            Some(*sum)
+        })
+        .collect::<Vec<_>>();
+    let log_tests = 2.5 * ((width * height) as f64).ln();
+    let p = TOLERANCE / PI;
+
+    let mut used = vec![vec![false; height]; width];
+    let mut segments = vec![];
+    for (sx, sy) in seeds {
+        if used[sx][sy] {
+            continue;
+        }
+        // Region growing over 8-neighbours with a similar level-line angle
+        let mut region = vec![(sx, sy)];
+        used[sx][sy] = true;
+        let (mut sum_cos, mut sum_sin) = (angle[sx][sy].cos(), angle[sx][sy].sin());
+        let mut n = 0;
+        while n < region.len() {
+            let (x, y) = region[n];
+            n += 1;
+            for (i, j) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let (nx, ny) = (x as i64 + i, y as i64 + j);
+                if nx < 0 || ny < 0 || nx >= width as i64 - 1 || ny >= height as i64 - 1 {
+                    continue;
+                }
+                let (nx, ny) = (nx as usize, ny as usize);
+                if used[nx][ny] || magnitude[nx][ny] <= MIN_GRADIENT
+                    || angle_diff(angle[nx][ny], sum_sin.atan2(sum_cos)) > TOLERANCE {
+                    continue;
+                }
+                used[nx][ny] = true;
+                sum_cos += angle[nx][ny].cos();
+                sum_sin += angle[nx][ny].sin();
+                region.push((nx, ny));
+            }
+        }
+        if region.len() < 5 {
+            continue;
+        }
+
+        // Rectangle around the region, along its principal axis
+        let total = region.iter().map(|&(x, y)| magnitude[x][y]).sum::<f64>();
+        let (cx, cy) = region.iter()
+            .fold((0.0, 0.0), |(cx, cy), &(x, y)| (cx + x as f64 * magnitude[x][y], cy + y as f64 * magnitude[x][y]));
+        let (cx, cy) = (cx / total, cy / total);
+        let (mut ixx, mut iyy, mut ixy) = (0.0, 0.0, 0.0);
+        for &(x, y) in &region {
+            let (dx, dy, w) = (x as f64 - cx, y as f64 - cy, magnitude[x][y]);
+            ixx += w * dy * dy;
+            iyy += w * dx * dx;
+            ixy -= w * dx * dy;
+        }
+        let lambda = 0.5 * (ixx + iyy - ((ixx - iyy).powi(2) + 4.0 * ixy * ixy).sqrt());
+        let mut theta = if ixx.abs() > iyy.abs() {
+            (lambda - ixx).atan2(ixy)
+        } else {
+            ixy.atan2(lambda - iyy)
+        };
+        let region_angle = sum_sin.atan2(sum_cos);
+        // Keep the direction of the level lines, which tells dark from light side
+        if angle_diff(theta, region_angle) > PI / 2.0 {
+            theta += PI;
+        }
+        let (dx, dy) = (theta.cos(), theta.sin());
+        let (mut l_min, mut l_max, mut w_min, mut w_max) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        for &(x, y) in &region {
+            let (px, py) = (x as f64 - cx, y as f64 - cy);
+            let l = px * dx + py * dy;
+            let w = -px * dy + py * dx;
+            l_min = l_min.min(l);
+            l_max = l_max.max(l);
+            w_min = w_min.min(w);
+            w_max = w_max.max(w);
+        }
+
+        // Count the aligned pixels among all of the rectangle's
+        let (mut trials, mut aligned) = (0, 0);
+        let corners = [(l_min, w_min), (l_min, w_max), (l_max, w_min), (l_max, w_max)]
+            .map(|(l, w)| (cx + l * dx - w * dy, cy + l * dy + w * dx));
+        let (x0, x1) = corners.iter().fold((f64::MAX, f64::MIN), |(a, b), c| (a.min(c.0), b.max(c.0)));
+        let (y0, y1) = corners.iter().fold((f64::MAX, f64::MIN), |(a, b), c| (a.min(c.1), b.max(c.1)));
+        let (xs, ys) = (x0.floor().max(0.0) as usize..=(x1.ceil() as usize).min(width - 2),
+                        y0.floor().max(0.0) as usize..=(y1.ceil() as usize).min(height - 2));
+        let inside = xs.flat_map(|x| ys.clone().map(move |y| (x, y)))
+            .filter(|&(x, y)| {
+                let (px, py) = (x as f64 - cx, y as f64 - cy);
+                let (l, w) = (px * dx + py * dy, -px * dy + py * dx);
+                l >= l_min - 0.5 && l <= l_max + 0.5 && w >= w_min - 0.5 && w <= w_max + 0.5
+            });
+        for (x, y) in inside {
+            trials += 1;
+            if angle_diff(angle[x][y], theta) <= TOLERANCE {
+                aligned += 1;
+            }
+        }
+        let significance = -(log_tests + log_binomial_tail(trials, aligned, p, &log_factorial)) / 10f64.ln();
+        if significance <= 0.0 {
+            continue;
+        }
+
+        // Gradients sit on pixel corners, half a pixel off
+        let middle = (w_min + w_max) / 2.0;
+        let point = |l: f64| (cx + l * dx - middle * dy + 0.5, cy + l * dy + middle * dx + 0.5);
+        segments.push(LineSegment {
+            start: point(l_min),
+            end: point(l_max),
+            width: w_max - w_min + 1.0,
+            significance,
+        });
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::lsd;
+
+    #[test]
+    fn finds_the_edge_of_a_step() {
+        let step = Image::from_fn(40, 30, |x, _| Rgba::gray(if x < 20 { 0.2 } else { 0.8 }));
+        let segments = lsd(&step);
+        assert_eq!(segments.len(), 1);
+        let segment = segments[0];
+        assert!((segment.start.0 - 20.0).abs() < 1.0 && (segment.end.0 - 20.0).abs() < 1.0);
+        assert!(segment.length() > 25.0);
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+use crate::cpu::Image;
+use crate::pipeline::Image as _;
+use crate::rgba::Rgba;
+use crate::StructuringElement;
+
+/// A binary image packed one bit per pixel. Pixels are stored column by
+/// column, matching the `[x][y]` layout of the images
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    bits: Vec<u64>,
+}
+
+impl Mask {
+    pub fn new(width: usize, height: usize) -> Mask {
+        Mask {
+            width,
+            height,
+            bits: vec![0; (width * height).div_ceil(64)],
+        }
+    }
+
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> bool) -> Mask {
+        let mut mask = Mask::new(width, height);
+        for x in 0..width {
+            for y in 0..height {
+                mask.set(x, y, f(x, y));
+            }
+        }
+        mask
+    }
+
+    /// The pixels whose intensity is above `threshold`
+    pub fn threshold(image: &Image, threshold: f64) -> Mask {
+        Mask::from_fn(image.width(), image.height(), |x, y| image[(x, y)].intensity() > threshold)
+    }
+
+    /// White where the mask is set, black elsewhere
+    pub fn to_image(&self) -> Image {
+        Image::from_fn(self.width, self.height, |x, y| if self.get(x, y) {
+            Rgba::WHITE
+        } else {
+            Rgba::BLACK
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let n = x * self.height + y;
+        self.bits[n / 64] >> (n % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        let n = x * self.height + y;
+        if value {
+            self.bits[n / 64] |= 1 << (n % 64);
+        } else {
+            self.bits[n / 64] &= !(1 << (n % 64));
+        }
+    }
+
+    /// Number of set pixels
+    pub fn count(&self) -> usize {
+        self.bits.iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Set where every pixel of `element` around it is set, treating pixels
+    /// outside of the mask as unset
+    pub fn erode(&self, element: StructuringElement) -> Mask {
+        let offsets = element.offsets();
+        Mask::from_fn(self.width, self.height, |x, y| offsets.iter()
+            .all(|&(i, j)| self.get_signed(x as i64 + i, y as i64 + j)))
+    }
+
+    /// Set where any pixel of `element` around it is set
+    pub fn dilate(&self, element: StructuringElement) -> Mask {
+        let offsets = element.offsets();
+        Mask::from_fn(self.width, self.height, |x, y| offsets.iter()
+            .any(|&(i, j)| self.get_signed(x as i64 - i, y as i64 - j)))
+    }
+
+    pub fn open(&self, element: StructuringElement) -> Mask {
+        self.erode(element)
+            .dilate(element)
+    }
+
+    pub fn close(&self, element: StructuringElement) -> Mask {
+        self.dilate(element)
+            .erode(element)
+    }
+
+    /// The 4-connected region of set pixels containing `(x, y)`, empty if
+    /// the pixel is unset
+    pub fn flood_fill(&self, x: usize, y: usize) -> Mask {
+        let mut region = Mask::new(self.width, self.height);
+        if !self.get(x, y) {
+            return region;
+        }
+        region.set(x, y, true);
+        let mut queue = VecDeque::from([(x, y)]);
+        while let Some((x, y)) = queue.pop_front() {
+            for (i, j) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                let (nx, ny) = (x as i64 + i, y as i64 + j);
+                if self.get_signed(nx, ny) && !region.get(nx as usize, ny as usize) {
+                    region.set(nx as usize, ny as usize, true);
+                    queue.push_back((nx as usize, ny as usize));
+                }
+            }
+        }
+        region
+    }
+
+    fn get_signed(&self, x: i64, y: i64) -> bool {
+        x >= 0 && y >= 0 && x < self.width as i64 && y < self.height as i64 && self.get(x as usize, y as usize)
+    }
+
+    fn zip(&self, other: &Mask, f: impl Fn(u64, u64) -> u64) -> Mask {
+        assert_eq!((self.width, self.height), (other.width, other.height), "Masks differ in size");
+        Mask {
+            width: self.width,
+            height: self.height,
+            bits: self.bits.iter()
+                .zip(&other.bits)
+                .map(|(a, b)| f(*a, *b))
+                .collect(),
+        }
+    }
+}
+
+/// Run-length encoding of a mask, as in COCO: the lengths of alternating
+/// runs of unset and set pixels, column by column, starting with unset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rle {
+    pub width: usize,
+    pub height: usize,
+    pub counts: Vec<usize>,
+}
+
+impl Rle {
+    pub fn encode(mask: &Mask) -> Rle {
+        let mut counts = vec![];
+        let (mut value, mut run) = (false, 0);
+        for x in 0..mask.width {
+            for y in 0..mask.height {
+                if mask.get(x, y) != value {
+                    counts.push(run);
+                    value = !value;
+                    run = 0;
+                }
+                run += 1;
+            }
+        }
+        counts.push(run);
+        Rle {
+            width: mask.width,
+            height: mask.height,
+            counts,
+        }
+    }
+
+    pub fn decode(&self) -> Mask {
+        let mut mask = Mask::new(self.width, self.height);
+        let mut n = 0;
+        for (run, count) in self.counts.iter().enumerate() {
+            if run % 2 == 1 {
+                for m in n..n + count {
+                    mask.set(m / self.height, m % self.height, true);
+                }
+            }
+            n += count;
+        }
+        mask
+    }
+
+    /// Number of set pixels
+    pub fn area(&self) -> usize {
+        self.counts.iter()
+            .skip(1)
+            .step_by(2)
+            .sum()
+    }
+
+    /// Number of pixels set in both masks, by walking the runs of both
+    pub fn intersection(&self, other: &Rle) -> usize {
+        assert_eq!((self.width, self.height), (other.width, other.height), "Masks differ in size");
+        let runs = |rle: &Rle| rle.counts.iter()
+            .enumerate()
+            .map(|(n, count)| (n % 2 == 1, *count))
+            .filter(|(_, count)| *count > 0)
+            .collect::<VecDeque<_>>();
+        let (mut a, mut b) = (runs(self), runs(other));
+        let mut both = 0;
+        while let (Some(&(set_a, left_a)), Some(&(set_b, left_b))) = (a.front(), b.front()) {
+            let step = left_a.min(left_b);
+            if set_a && set_b {
+                both += step;
+            }
+            for (runs, left) in [(&mut a, left_a), (&mut b, left_b)] {
+                if left == step {
+                    runs.pop_front();
+                } else {
+                    runs[0].1 -= step;
+                }
+            }
+        }
+        both
+    }
+
+    /// Intersection over union, `0` for two empty masks
+    pub fn iou(&self, other: &Rle) -> f64 {
+        let both = self.intersection(other);
+        let union = self.area() + other.area() - both;
+        if union == 0 {
+            0.0
+        } else {
+            both as f64 / union as f64
+        }
+    }
+}
+
+impl BitAnd for &Mask {
+    type Output = Mask;
+
+    fn bitand(self, other: &Mask) -> Mask {
+        self.zip(other, |a, b| a & b)
+    }
+}
+
+impl BitOr for &Mask {
+    type Output = Mask;
+
+    fn bitor(self, other: &Mask) -> Mask {
+        self.zip(other, |a, b| a | b)
+    }
+}
+
+impl BitXor for &Mask {
+    type Output = Mask;
+
+    fn bitxor(self, other: &Mask) -> Mask {
+        self.zip(other, |a, b| a ^ b)
+    }
+}
+
+impl Not for &Mask {
+    type Output = Mask;
+
+    fn not(self) -> Mask {
+        let mut mask = Mask {
+            width: self.width,
+            height: self.height,
+            bits: self.bits.iter().map(|word| !word).collect(),
+        };
+        // Keep the padding after the last pixel clear, so `count` stays right
+        let used = self.width * self.height % 64;
+        if let (Some(last), true) = (mask.bits.last_mut(), used != 0) {
+            *last &= (1 << used) - 1;
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mask, Rle};
+
+    #[test]
+    fn boolean_ops_and_counting() {
+        let left = Mask::from_fn(5, 3, |x, _| x < 2);
+        let top = Mask::from_fn(5, 3, |_, y| y == 0);
+        assert_eq!(left.count(), 6);
+        assert_eq!((&left & &top).count(), 2);
+        assert_eq!((&left | &top).count(), 9);
+        assert_eq!((&left ^ &top).count(), 7);
+        assert_eq!((!&left).count(), 9);
+        assert_eq!(left.flood_fill(0, 2), left);
+    }
+
+    #[test]
+    fn rle_round_trip_and_iou() {
+        let square = Mask::from_fn(6, 4, |x, y| (1..4).contains(&x) && y >= 1);
+        let shifted = Mask::from_fn(6, 4, |x, y| (2..5).contains(&x) && y >= 1);
+        let rle = Rle::encode(&square);
+        assert_eq!(rle.decode(), square);
+        assert_eq!(rle.area(), square.count());
+        assert_eq!(rle.iou(&Rle::encode(&shifted)), 6.0 / 12.0);
+    }
+}
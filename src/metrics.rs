@@ -0,0 +1,130 @@
+use crate::cpu::Image;
+use crate::integral::IntegralImage;
+use crate::rgba::Rgba;
+use crate::BorderMode;
+
+/// Stabilizing constants of SSIM for a dynamic range of `1.0`
+const C1: f64 = 0.01 * 0.01;
+const C2: f64 = 0.03 * 0.03;
+
+fn check_sizes(a: &Image, b: &Image) {
+    assert_eq!((a.width(), a.height()), (b.width(), b.height()),
+               "Compared images differ in size");
+}
+
+/// Mean squared error over the colour channels of two images of the same
+/// size. Alpha is ignored
+pub fn mse(a: &Image, b: &Image) -> f64 {
+    check_sizes(a, b);
+    let sum = (0..a.width())
+        .flat_map(|x| (0..a.height())
+            .map(move |y| (x, y)))
+        .map(|p| {
+            let (a, b) = (a[p], b[p]);
+            [a.red() - b.red(), a.green() - b.green(), a.blue() - b.blue()]
+                .iter()
+                .map(|d| d * d)
+                .sum::<f64>()
+        })
+        .sum::<f64>();
+    sum / (3 * a.width() * a.height()).max(1) as f64
+}
+
+/// Peak signal to noise ratio in decibels, with a peak of `1.0`. Infinite
+/// for identical images
+pub fn psnr(a: &Image, b: &Image) -> f64 {
+    -10.0 * mse(a, b).log10()
+}
+
+/// Structural similarity of the colour channels at every pixel, over the
+/// `window` by `window` square around it, clipped to the image. Even
+/// windows grow by one. Alpha is set to `1.0`
+pub fn ssim_map(a: &Image, b: &Image, window: usize) -> Image {
+    check_sizes(a, b);
+    let radius = window / 2;
+    let (sums_a, squares_a) = (IntegralImage::new(a), IntegralImage::squares(a));
+    let (sums_b, squares_b) = (IntegralImage::new(b), IntegralImage::squares(b));
+    let products = IntegralImage::new(&a.similar(|x, y| a[(x, y)] * b[(x, y)]));
+    a.similar(|x, y| {
+        let (mean_a, mean_b) = (sums_a.mean(x, y, radius), sums_b.mean(x, y, radius));
+        let covariance: [f64; 4] = (products.mean(x, y, radius) - mean_a * mean_b).into();
+        let (mean_a, mean_b): ([f64; 4], [f64; 4]) = (mean_a.into(), mean_b.into());
+        let variance_a: [f64; 4] = sums_a.variance(&squares_a, x, y, radius).into();
+        let variance_b: [f64; 4] = sums_b.variance(&squares_b, x, y, radius).into();
+        let channel = |c: usize| (2.0 * mean_a[c] * mean_b[c] + C1) * (2.0 * covariance[c] + C2)
+            / ((mean_a[c] * mean_a[c] + mean_b[c] * mean_b[c] + C1) * (variance_a[c] + variance_b[c] + C2));
+        Rgba::from((channel(0), channel(1), channel(2), 1.0))
+    })
+}
+
+/// Mean of `ssim_map`: `1.0` for identical images, lower the more their
+/// local structure differs
+pub fn ssim(a: &Image, b: &Image, window: usize) -> f64 {
+    let map = ssim_map(a, b, window);
+    let sum = (0..map.width())
+        .flat_map(|x| (0..map.height())
+            .map(move |y| (x, y)))
+        .map(|p| map[p].red() + map[p].green() + map[p].blue())
+        .sum::<f64>();
+    sum / (3 * map.width() * map.height()).max(1) as f64
+}
+
+/// Variance of the four-neighbour Laplacian of the intensity. Sharper
+/// images score higher, so out of focus frames can be rejected below a
+/// threshold found for the camera at hand
+pub fn focus_measure(image: &Image) -> f64 {
+    let intensity = |x: i64, y: i64| image.get(x, y, BorderMode::Replicate).intensity();
+    let responses = (0..image.width() as i64)
+        .flat_map(|x| (0..image.height() as i64)
+            .map(move |y| (x, y)))
+        .map(|(x, y)| intensity(x - 1, y) + intensity(x + 1, y) + intensity(x, y - 1) + intensity(x, y + 1)
+            - 4.0 * intensity(x, y))
+        .collect::<Vec<_>>();
+    let count = responses.len().max(1) as f64;
+    let mean = responses.iter().sum::<f64>() / count;
+    responses.iter()
+        .map(|r| (r - mean) * (r - mean))
+        .sum::<f64>() / count
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::rgba::Rgba;
+    use crate::cpu::CpuPipeline;
+    use crate::pipeline::Pipeline;
+    use super::{focus_measure, mse, psnr, ssim};
+
+    #[test]
+    fn uniform_offset() {
+        let gray = |v: f64| Image::from_weights(&vec![vec![v; 4]; 3]);
+        let (a, b) = (gray(0.5), gray(0.6));
+        assert!((mse(&a, &b) - 0.01).abs() < 1e-12);
+        assert!((psnr(&a, &b) - 20.0).abs() < 1e-9);
+        assert_eq!(psnr(&a, &a), f64::INFINITY);
+        assert!((ssim(&a, &a, 3) - 1.0).abs() < 1e-12);
+        assert!(ssim(&a, &b, 3) < 1.0);
+    }
+
+    #[test]
+    fn ssim_prefers_structure_over_offset() {
+        let stripes = Image::from_weights(&(0..8)
+            .map(|_| (0..8).map(|x| if x % 2 == 0 { 0.3 } else { 0.7 }).collect())
+            .collect::<Vec<_>>());
+        let brighter = stripes.similar(|x, y| stripes[(x, y)] + Rgba::gray(0.3));
+        let flat = Image::from_weights(&vec![vec![0.5; 8]; 8]);
+        // The flat image is closer in MSE but loses all of the structure
+        assert!(mse(&stripes, &flat) < mse(&stripes, &brighter));
+        assert!(ssim(&stripes, &brighter, 3) > ssim(&stripes, &flat, 3));
+    }
+
+    #[test]
+    fn blurring_lowers_focus() {
+        let checkers = Image::from_weights(&(0..16)
+            .map(|y| (0..16).map(|x| ((x / 2 + y / 2) % 2) as f64).collect())
+            .collect::<Vec<_>>());
+        let blurred = CpuPipeline::default().average_blur(3).apply(&checkers);
+        assert!(focus_measure(&checkers) > 2.0 * focus_measure(&blurred));
+        assert_eq!(focus_measure(&Image::from_weights(&vec![vec![0.5; 4]; 4])), 0.0);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::cpu::Image;
+use crate::pipeline::Image as _;
+use crate::rgba::Rgba;
+
+/// Up to `colors` colours representing the image, by median cut: the
+/// pixels are split at the median of their widest colour channel until
+/// there are enough groups, and each group gives its mean colour
+pub fn median_cut(image: &Image, colors: usize) -> Vec<Rgba> {
+    let pixels = (0..image.width())
+        .flat_map(|x| (0..image.height())
+            .map(move |y| (x, y)))
+        .map(|p| {
+            let [r, g, b, _]: [f64; 4] = image[p].into();
+            [r, g, b]
+        })
+        .collect::<Vec<_>>();
+    let range = |group: &[[f64; 3]], c: usize| {
+        let (min, max) = group.iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), p| (min.min(p[c]), max.max(p[c])));
+        max - min
+    };
+    // The widest channel of a group and how wide it is
+    let widest = |group: &[[f64; 3]]| (0..3)
+        .map(|c| (c, range(group, c)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+
+    let mut groups = vec![pixels];
+    while groups.len() < colors {
+        let Some((n, (channel, _))) = groups.iter()
+            .enumerate()
+            .filter(|(_, group)| group.len() > 1)
+            .map(|(n, group)| (n, widest(group)))
+            .filter(|(_, (_, width))| *width > 0.0)
+            .max_by(|a, b| a.1.1.total_cmp(&b.1.1)) else {
+            break;
+        };
+        let mut group = groups.swap_remove(n);
+        group.sort_by(|a, b| a[channel].total_cmp(&b[channel]));
+        // Cut at the median, keeping equal values on the same side
+        let median = group[group.len() / 2][channel];
+        let cut = match group.iter().position(|p| p[channel] >= median) {
+            Some(0) | None => group.iter().position(|p| p[channel] > median).unwrap(),
+            Some(cut) => cut,
+        };
+        let upper = group.split_off(cut);
+        groups.push(group);
+        groups.push(upper);
+    }
+
+    groups.iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| {
+            let sum = group.iter()
+                .fold([0.0; 3], |sum, p| [sum[0] + p[0], sum[1] + p[1], sum[2] + p[2]]);
+            let n = group.len() as f64;
+            (sum[0] / n, sum[1] / n, sum[2] / n, 1.0).into()
+        })
+        .collect()
+}
+
+/// The palette colour closest to `color`, keeping its alpha
+pub fn nearest(palette: &[Rgba], color: Rgba) -> Rgba {
+    palette.get(closest(palette, color))
+        .map_or(color, |p| p.with_alpha(color.alpha()))
+}
+
+/// A colour of an image and how many of its pixels are closest to it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Swatch {
+    pub color: Rgba,
+    pub count: usize,
+}
+
+/// The `colors` dominant colours of the image by k-means, most common
+/// first. The clusters start from the median cut palette, so the result
+/// does not change between runs
+pub fn extract_palette(image: &Image, colors: usize) -> Vec<Swatch> {
+    const ITERATIONS: usize = 10;
+    let pixels = (0..image.width())
+        .flat_map(|x| (0..image.height())
+            .map(move |y| (x, y)))
+        .map(|p| image[p].with_alpha(1.0))
+        .collect::<Vec<_>>();
+    let mut centres = median_cut(image, colors);
+    let mut counts = vec![0; centres.len()];
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![Rgba::gray(0.0); centres.len()];
+        counts = vec![0; centres.len()];
+        for pixel in &pixels {
+            let n = closest(&centres, *pixel);
+            sums[n] = sums[n] + *pixel;
+            counts[n] += 1;
+        }
+        let moved = centres.iter()
+            .zip(sums.iter().zip(&counts))
+            .map(|(centre, (sum, count))| match count {
+                0 => *centre,
+                _ => (*sum / *count as f64).with_alpha(1.0),
+            })
+            .collect::<Vec<_>>();
+        if moved == centres {
+            break;
+        }
+        centres = moved;
+    }
+    let mut swatches = centres.into_iter()
+        .zip(counts)
+        .map(|(color, count)| Swatch { color, count })
+        .collect::<Vec<_>>();
+    swatches.sort_by_key(|swatch| std::cmp::Reverse(swatch.count));
+    swatches
+}
+
+/// The swatches as `size` squares side by side
+pub fn render_swatches(swatches: &[Swatch], size: usize) -> Image {
+    Image::from_fn(swatches.len() * size, size, |x, _| swatches[x / size].color)
+}
+
+/// Index of the palette colour closest to `color`
+fn closest(palette: &[Rgba], color: Rgba) -> usize {
+    let distance = |p: &Rgba| (*p - color).into_iter()
+        .take(3)
+        .map(|d| d * d)
+        .sum::<f64>();
+    (0..palette.len())
+        .min_by(|a, b| distance(&palette[*a]).total_cmp(&distance(&palette[*b])))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::{extract_palette, median_cut, nearest};
+
+    #[test]
+    fn two_colours_survive_median_cut() {
+        let image = Image::from_fn(6, 4, |x, _| if x < 2 { Rgba::RED } else { Rgba::BLUE });
+        let palette = median_cut(&image, 4);
+        assert_eq!(palette.len(), 2);
+        assert!(palette.contains(&Rgba::RED) && palette.contains(&Rgba::BLUE));
+        assert_eq!(nearest(&palette, (0.1, 0.0, 0.6, 0.5).into()), Rgba::BLUE.with_alpha(0.5));
+
+        let swatches = extract_palette(&image, 2);
+        assert_eq!((swatches[0].color, swatches[0].count), (Rgba::BLUE, 16));
+        assert_eq!((swatches[1].color, swatches[1].count), (Rgba::RED, 8));
+    }
+}
@@ -0,0 +1,97 @@
+use std::f64::consts::PI;
+use crate::cpu::Image;
+
+/// Mean intensity of each cell of a `width`x`height` grid laid over the
+/// image, indexed `[x][y]`
+fn shrink(image: &Image, width: usize, height: usize) -> Vec<Vec<f64>> {
+    // Pixels `[start, end)` of cell `n` of `cells` along a side of `size`
+    let span = |n: usize, cells: usize, size: usize| {
+        let start = (n * size / cells).min(size.saturating_sub(1));
+        (start, ((n + 1) * size / cells).clamp(start + 1, size.max(1)))
+    };
+    (0..width)
+        .map(|i| (0..height)
+            .map(|j| {
+                let (x0, x1) = span(i, width, image.width());
+                let (y0, y1) = span(j, height, image.height());
+                let sum = (x0..x1)
+                    .flat_map(|x| (y0..y1)
+                        .map(move |y| (x, y)))
+                    .map(|p| image[p].intensity())
+                    .sum::<f64>();
+                sum / ((x1 - x0) * (y1 - y0)) as f64
+            })
+            .collect())
+        .collect()
+}
+
+/// One bit per value, set where `on` holds, row by row
+fn bits(values: impl IntoIterator<Item = bool>) -> u64 {
+    values.into_iter()
+        .enumerate()
+        .fold(0, |hash, (n, on)| hash | (on as u64) << n)
+}
+
+/// Average hash: which cells of an 8x8 grid are brighter than the mean
+pub fn ahash(image: &Image) -> u64 {
+    let cells = shrink(image, 8, 8);
+    let mean = cells.iter().flatten().sum::<f64>() / 64.0;
+    bits((0..8).flat_map(|y| (0..8).map(move |x| (x, y)))
+        .map(|(x, y)| cells[x][y] > mean))
+}
+
+/// Difference hash: which cells of a 9x8 grid are brighter than their
+/// right neighbour
+pub fn dhash(image: &Image) -> u64 {
+    let cells = shrink(image, 9, 8);
+    bits((0..8).flat_map(|y| (0..8).map(move |x| (x, y)))
+        .map(|(x, y)| cells[x][y] > cells[x + 1][y]))
+}
+
+/// Perceptual hash: which of the 8x8 lowest frequencies of the DCT of a
+/// 32x32 grid are above their median, leaving out the constant term
+pub fn phash(image: &Image) -> u64 {
+    const SIZE: usize = 32;
+    let cells = shrink(image, SIZE, SIZE);
+    let basis = |k: usize, n: usize| (PI * (2 * n + 1) as f64 * k as f64 / (2 * SIZE) as f64).cos();
+    let coefficients = (0..8)
+        .flat_map(|v| (0..8).map(move |u| (u, v)))
+        .map(|(u, v)| (0..SIZE)
+            .flat_map(|x| (0..SIZE).map(move |y| (x, y)))
+            .map(|(x, y)| cells[x][y] * basis(u, x) * basis(v, y))
+            .sum::<f64>())
+        .collect::<Vec<_>>();
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    bits(coefficients.iter()
+        .enumerate()
+        .map(|(n, c)| n > 0 && *c > median))
+}
+
+/// Number of differing bits, small for similar images
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::{ahash, dhash, hamming, phash};
+
+    #[test]
+    fn similar_images_hash_close() {
+        let image = Image::from_fn(64, 48, |x, y| {
+            let blob = (-((x as f64 - 20.0).powi(2) + (y as f64 - 30.0).powi(2)) / 200.0).exp();
+            Rgba::gray(0.7 * blob + x as f64 / 256.0 + y as f64 / 512.0)
+        });
+        let brighter = Image::from_fn(64, 48, |x, y| image[(x, y)] * 0.9 + Rgba::gray(0.05));
+        let other = Image::from_fn(64, 48, |x, y| Rgba::gray(((x * y) % 17) as f64 / 17.0));
+        for hash in [ahash, dhash, phash] {
+            assert!(hamming(hash(&image), hash(&brighter)) <= 4);
+            assert!(hamming(hash(&image), hash(&other)) > 10);
+        }
+    }
+}
@@ -1,41 +1,366 @@
 use crate::rgba::Rgba;
-use crate::Filter;
+use crate::{Anchor, Axis, BorderMode, ChannelCorrection, ColorVisionDeficiency, DitherMethod, Filter, FrequencyResponse, GradientNorm, Interpolation, NoiseChannels, Orientation, OutputSize, ReduceOp, Shrinkage, StructuringElement};
 
 pub trait Image {
     fn black(width: usize, height: usize) -> Self;
+    fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> Rgba) -> Self;
+}
+
+/// What the pixels of an image stand for, so that stages can tell when
+/// they are given something they were not meant for
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Content {
+    #[default]
+    Color,
+    Grayscale,
+    GradientMagnitude,
+    /// Black and white only
+    BinaryMask,
+    /// Regions in distinct colours, such as `LabelImage::to_image` makes
+    Labels,
+}
+
+/// What a pipeline needs to know about its input to be validated
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub width: usize,
+    pub height: usize,
+    pub content: Content,
+}
+
+impl ImageInfo {
+    /// A colour image of the given size
+    pub fn new(width: usize, height: usize) -> ImageInfo {
+        ImageInfo { width, height, content: Content::Color }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The stage will run, but likely not as intended
+    Warning,
+    /// The stage will panic or produce garbage
+    Error,
+}
+
+/// A problem found by `Pipeline::validate` in the stage at index `stage`
+#[derive(Clone, Debug)]
+pub struct ValidationIssue {
+    pub stage: usize,
+    pub severity: Severity,
+    pub message: String,
 }
 
 pub trait Pipeline: Sized {
     type Image: Image;
     fn filter(self, needle: Filter<Self>) -> Self;
+    /// Correlates the image with `kernel`, placing the kernel's `anchor` on
+    /// each pixel and combining the weighted taps with `reduce`
+    fn convolve(self, kernel: &Self::Image, anchor: Anchor, border: BorderMode, reduce: ReduceOp) -> Self;
+    /// Blurs with a `size` by `size` gaussian of the given `variance`, in
+    /// pixels squared
     fn gaussian_blur(self, size: usize, variance: f64) -> Self;
+    /// Keeps the spatial frequencies below `cutoff`, in cycles per pixel
+    /// up to 0.5, by filtering the image's spectrum
+    fn freq_lowpass(self, response: FrequencyResponse, cutoff: f64) -> Self;
+    /// Keeps the spatial frequencies above `cutoff`. The result has no
+    /// average, so its colours lie around zero
+    fn freq_highpass(self, response: FrequencyResponse, cutoff: f64) -> Self;
+    /// Rejects the periodic noise at each `(u, v)` frequency of `notches`,
+    /// in cycles per pixel along x and y, with a gaussian notch of `radius`
+    fn notch_reject(self, notches: Vec<(f64, f64)>, radius: f64) -> Self;
+    /// Removes noise by shrinking the detail coefficients of a Haar wavelet
+    /// transform over `levels` levels. Unlike a blur, strong edges survive
+    fn wavelet_denoise(self, levels: usize, threshold: f64, shrinkage: Shrinkage) -> Self;
+    /// Mean over a `size` square window. Unlike filtering with
+    /// `Generator::average_needle`, the cost does not grow with `size`
+    fn average_blur(self, size: usize) -> Self {
+        self.box_filter_integral(size)
+    }
+    /// Edge preserving blur: neighbours are weighted by both their distance
+    /// (`spatial_sigma`, in pixels) and their colour difference (`range_sigma`)
+    fn bilateral(self, spatial_sigma: f64, range_sigma: f64) -> Self;
+    /// Sharpens as `original + amount * (original - blurred)`, blurring with
+    /// a gaussian of standard deviation `radius`. Pixels that differ from
+    /// the blurred image by no more than `threshold` are left alone
+    fn unsharp_mask(self, radius: f64, amount: f64, threshold: f64) -> Self;
     fn offset(self, x: i64, y: i64) -> Self;
+    /// Sets how the filters and offsets added after this one sample pixels
+    /// outside of the image. `BorderMode::Replicate` until set
+    fn border(self, mode: BorderMode) -> Self;
+    /// Sets the output size of the filters added after this one, so that
+    /// they can shrink to where their window fits or grow to every overlap.
+    /// `OutputSize::Same` until set
+    fn output_size(self, mode: OutputSize) -> Self;
+    /// Undoes `orientation`, turning the stored pixels upright
+    fn orient(self, orientation: Orientation) -> Self;
+    /// Halves both dimensions after a binomial blur, see `cpu::Image::pyr_down`
+    fn pyr_down(self) -> Self;
+    /// Resamples to `width` by `height`, see `cpu::Image::resize`
+    fn resize(self, width: usize, height: usize, interpolation: Interpolation) -> Self;
+    /// Shrinks both dimensions by `factor` after a gaussian blur that
+    /// prevents aliasing
+    fn decimate(self, factor: usize) -> Self;
+    /// Frames the image with `size` pixels of `color` on every side
+    fn add_border(self, size: usize, color: Rgba) -> Self;
+    /// Letterboxes the image with `color`, keeping it centred, until its
+    /// width divided by its height is `ratio`
+    fn pad_to_aspect(self, ratio: f64, color: Rgba) -> Self;
+    /// Runs `stage` on the image only when `condition` holds for its width
+    /// and height, so one pipeline suits both thumbnails and full scans
+    fn when(self, condition: impl Fn(usize, usize) -> bool + 'static, stage: Self) -> Self;
+    /// Gaussian blur of `sigma` on images of more than `megapixels` million pixels
+    fn blur_if_larger_than(self, megapixels: f64, sigma: f64) -> Self;
+    /// Attaches a weight map to the stages added after this one. Its
+    /// intensity is the confidence of each pixel: filtering with convolution
+    /// kernels, such as `gaussian_blur`, and the box blurs `average_blur` and
+    /// `box_filter_integral` become weighted averages, and `add` and `blend`
+    /// scale the other image by it. Other stages ignore it
+    fn with_weights(self, weights: Self::Image) -> Self;
     fn add(self, other: Self) -> Self;
     fn sub(self, other: Self) -> Self;
-    fn ennoise(self, noise: Self) -> Self;
+    /// Mixes in `amount` of the other image, `0` keeping this one and `1`
+    /// replacing it
+    fn blend(self, other: Self, amount: f64) -> Self;
+    /// Absolute difference to the other image on every colour channel,
+    /// multiplied by `amplify` and clipped to 1 so that small changes show.
+    /// Colorized, the intensity of the difference goes from blue through
+    /// green to red instead
+    fn diff(self, other: Self, amplify: f64, colorize: bool) -> Self;
+    fn ennoise(self, noise: Self) -> Self {
+        self.ennoise_with(noise, NoiseChannels::All, None)
+    }
+    /// Adds noise centred around gray to the selected channels. With a
+    /// mask, the noise is scaled by the mask's intensity at each pixel
+    fn ennoise_with(self, noise: Self, channels: NoiseChannels, mask: Option<Self::Image>) -> Self;
     fn dim(self, factor: Rgba) -> Self;
+    /// Per-channel minimum over `element`, shrinking bright regions
+    fn erode(self, element: StructuringElement) -> Self;
+    /// Per-channel maximum over `element`, growing bright regions
+    fn dilate(self, element: StructuringElement) -> Self;
+    /// Erosion followed by dilation, removing bright specks smaller than `element`
+    fn open(self, element: StructuringElement) -> Self {
+        self.erode(element)
+            .dilate(element)
+    }
+    /// Dilation followed by erosion, filling dark holes smaller than `element`
+    fn close(self, element: StructuringElement) -> Self {
+        self.dilate(element)
+            .erode(element)
+    }
+    /// What opening removes: the bright details smaller than `element`
+    fn tophat(self, element: StructuringElement) -> Self;
+    /// What closing fills: the dark details smaller than `element`
+    fn blackhat(self, element: StructuringElement) -> Self;
+    /// Thins the regions brighter than half intensity down to one pixel wide
+    /// skeletons with the Zhang-Suen algorithm. The result is white on black
+    fn skeletonize(self) -> Self;
+    /// Frangi vesselness: how much each pixel looks like a bright ridge of
+    /// width around one of `scales`, from the eigenvalues of the Hessian.
+    /// Invert first to find dark ridges such as cracks
+    fn frangi(self, scales: Vec<f64>) -> Self;
     fn grayscale(self) -> Self;
-    fn gradient(self) -> Self;
+    /// Warm brown tones, as in old photographs
+    fn sepia(self) -> Self;
+    /// Per-channel gradient magnitude from central differences.
+    /// With `normalize`, the result is divided by the largest magnitude
+    /// the norm can produce, so it stays within [0, 1]
+    fn gradient_magnitude(self, norm: GradientNorm, normalize: bool) -> Self;
+    fn gradient(self) -> Self {
+        self.gradient_magnitude(GradientNorm::L2, true)
+    }
+    /// Gradient magnitude from a pair of derivative kernels, such as the
+    /// ones built by `Generator::sobel_x` and `Generator::sobel_y`. Other
+    /// filters are reported by `validate` and leave the image as it is
+    fn gradient_with(self, x: Filter<Self>, y: Filter<Self>, norm: GradientNorm, normalize: bool) -> Self;
+    /// Orientation of the luminance gradient as gray, with `0.0` and `1.0`
+    /// standing for an angle of -pi and pi radians respectively
+    fn gradient_direction(self) -> Self;
     fn invert(self) -> Self;
     fn non_max_suppress(self) -> Self;
     fn quantize(self, thresholds: Vec<f64>) -> Self;
-    fn canny(self, thresholds: Vec<f64>) -> Self {
+    /// Reduces the image to at most `colors` colours picked by median cut,
+    /// see `palette::median_cut`
+    fn quantize_colors(self, colors: usize) -> Self;
+    /// Marks where the intensity changes sign between neighbours by more than
+    /// `threshold`, on the pixel closer to zero. Applied to a Laplacian of
+    /// gaussian response, this is the Marr-Hildreth edge detector
+    fn zero_crossings(self, threshold: f64) -> Self;
+    /// Removes colour casts under the gray-world assumption: every colour
+    /// channel is scaled so that the channel means become equal
+    fn auto_white_balance(self) -> Self;
+    /// Like `auto_white_balance`, but equalizes the `percentile` (in [0, 100])
+    /// of every channel instead of its mean, which follows the highlights
+    /// and is less swayed by large coloured areas
+    fn auto_white_balance_percentile(self, percentile: f64) -> Self;
+    /// Stretches every colour channel so that its `clip_percent` percentile
+    /// becomes black and its `100 - clip_percent` percentile white
+    fn auto_levels(self, clip_percent: f64) -> Self;
+    /// Spreads the luminance evenly over [0, 1] by histogram equalization,
+    /// scaling the colour channels together so that hues are kept
+    fn equalize_hist(self) -> Self;
+    /// Remaps every colour channel so that its histogram matches the one of
+    /// the same channel in `reference`
+    fn match_histogram(self, reference: &Self::Image) -> Self;
+    /// Rounds every colour channel to `levels_per_channel` evenly spaced values
+    fn posterize(self, levels_per_channel: usize) -> Self;
+    /// Scales every colour channel around middle gray by `contrast`, then adds
+    /// `brightness`, clipping to [0, 1]
+    fn brightness_contrast(self, brightness: f64, contrast: f64) -> Self;
+    /// Raises every colour channel to the power of `1 / g`, so that a `g`
+    /// above one brightens the midtones
+    fn gamma(self, g: f64) -> Self;
+    /// Maps `in_black` and `in_white` to `out_black` and `out_white` on every
+    /// colour channel, linearly in between and clipping outside
+    fn levels(self, in_black: f64, in_white: f64, out_black: f64, out_white: f64) -> Self;
+    /// Stretches the colour channels linearly so that their lowest value is 0
+    /// and their highest 1, all by the same amount to keep hues. Rescales
+    /// gradients and other outputs that would be clipped on saving. Flat
+    /// images are left as they are
+    fn normalize(self) -> Self;
+    /// Inverts the colour channels at or above `threshold`
+    fn solarize(self, threshold: f64) -> Self;
+    /// Double thresholding: pixels at or above `high` are edges, and pixels
+    /// at or above `low` are kept only when connected to an edge
+    fn hysteresis(self, low: f64, high: f64) -> Self;
+    /// Hysteresis with the high threshold picked by Otsu's method over the
+    /// non-zero intensities, and the low threshold at half of it
+    fn hysteresis_otsu(self) -> Self;
+    /// Hysteresis with thresholds at `(1 - sigma)` and `(1 + sigma)` times
+    /// the median of the non-zero intensities
+    fn hysteresis_median(self, sigma: f64) -> Self;
+    fn dither(self, levels: usize, method: DitherMethod) -> Self;
+    /// Floyd-Steinberg error diffusion to the closest colours of `palette`,
+    /// e.g. black and white for 1-bit output
+    fn dither_palette(self, palette: Vec<Rgba>) -> Self;
+    fn correct_chromatic_aberration(self, red: ChannelCorrection, blue: ChannelCorrection) -> Self;
+    fn despeckle(self, threshold: f64) -> Self;
+    /// Mean over a `size` square window, constant time per pixel
+    fn box_filter_integral(self, size: usize) -> Self;
+    /// Binarizes each pixel against the mean of its `size` window minus `offset`
+    fn adaptive_mean_threshold(self, size: usize, offset: f64) -> Self;
+    /// Niblack binarization: the threshold is the `size` window mean plus
+    /// `k` times its standard deviation. `k` is usually around `-0.2`
+    fn niblack_threshold(self, size: usize, k: f64) -> Self;
+    /// Sauvola binarization for documents: the threshold is the `size` window
+    /// mean times `1 + k * (deviation / 0.5 - 1)`, so that flat background stays
+    /// white. `k` is usually between `0.2` and `0.5`
+    fn sauvola_threshold(self, size: usize, k: f64) -> Self;
+    /// Binarizes the image at the threshold picked by Otsu's method over
+    /// all intensities
+    fn threshold_otsu(self) -> Self;
+    fn simulate_cvd(self, deficiency: ColorVisionDeficiency) -> Self;
+    fn canny(self, low: f64, high: f64) -> Self {
+        self.canny_strength()
+            .hysteresis(low, high)
+    }
+    /// The edge strength `canny` thresholds: the gradient magnitude left
+    /// after non-maximum suppression, for custom thresholds or display
+    fn canny_strength(self) -> Self {
         self.grayscale()
-            .gaussian_blur(5, 0.6)
+            .gaussian_blur(5, 0.36)
             .gradient()
             .non_max_suppress()
-            .quantize(thresholds)
     }
+    /// Like `canny`, with thresholds derived from the image's gradients
+    fn auto_canny(self) -> Self {
+        self.canny_strength()
+            .hysteresis_otsu()
+    }
+    /// Like `canny`, with the classic median heuristic for the thresholds.
+    /// A `sigma` of 0.33 gives thresholds at 0.66 and 1.33 times the median
+    fn canny_auto_sigma(self, sigma: f64) -> Self {
+        self.canny_strength()
+            .hysteresis_median(sigma)
+    }
+    /// Like `canny`, with the gradient taken by the given derivative kernels
+    fn canny_with(self, x: Filter<Self>, y: Filter<Self>, low: f64, high: f64) -> Self {
+        self.canny_strength_with(x, y)
+            .hysteresis(low, high)
+    }
+    /// Like `canny_strength`, with the gradient taken by the given derivative kernels
+    fn canny_strength_with(self, x: Filter<Self>, y: Filter<Self>) -> Self {
+        self.grayscale()
+            .gaussian_blur(5, 0.36)
+            .gradient_with(x, y, GradientNorm::L2, true)
+            .non_max_suppress()
+    }
+    /// Checks the stage parameters against an input of the given size,
+    /// without running any of the stages
+    fn validate(&self, info: &ImageInfo) -> Vec<ValidationIssue>;
     fn apply(self, image: &Self::Image) -> Self::Image;
+    /// Applies the pipeline and lists the pixels with an intensity above
+    /// `threshold` as `(x, y, intensity)`, row by row
+    fn collect_edges(self, image: &Self::Image, threshold: f64) -> Vec<(usize, usize, f64)>;
+    /// Applies the pipeline once and feeds its output to every branch,
+    /// e.g. to get both the gradient magnitude and direction of an image
+    fn apply_multi(self, image: &Self::Image, branches: Vec<Self>) -> Vec<Self::Image> {
+        let base = self.apply(image);
+        branches.into_iter()
+            .map(|branch| branch.apply(&base))
+            .collect()
+    }
+    /// Runs the pipeline made by `build` over every image, spread across
+    /// threads. Pipelines run once, so every image gets its own
+    fn apply_batch(build: impl Fn() -> Self + Sync, images: &[Self::Image]) -> Vec<Self::Image>
+        where Self::Image: Send + Sync {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get());
+        let chunk = images.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            let workers = images.chunks(chunk)
+                .map(|chunk| scope.spawn(|| chunk.iter()
+                    .map(|image| build().apply(image))
+                    .collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            workers.into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        })
+    }
+    /// Like `apply_batch`, but lazily and one image at a time, for inputs
+    /// that do not fit in memory together
+    fn apply_stream<'a>(build: impl Fn() -> Self + 'a, images: impl IntoIterator<Item = Self::Image> + 'a) -> impl Iterator<Item = Self::Image> + 'a {
+        images.into_iter()
+            .map(move |image| build().apply(&image))
+    }
     fn generate(self, width: usize, height: usize) -> Self::Image {
         self.apply(&Image::black(width, height))
     }
+    /// Like `generate`, but starts from an image filled with `seed`
+    fn generate_from(self, width: usize, height: usize, seed: Rgba) -> Self::Image {
+        self.generate_with(width, height, move |_, _| seed)
+    }
+    /// Like `generate`, but starts from an image built pixel by pixel
+    fn generate_with(self, width: usize, height: usize, f: impl Fn(usize, usize) -> Rgba) -> Self::Image {
+        self.apply(&Image::from_fn(width, height, f))
+    }
 }
 
 pub trait Generator {
     type Pipeline: Pipeline;
     fn gaussian_noise(&self, mean: f64, variance: f64, intensity: f64) -> Self::Pipeline;
     fn salt_and_pepper_noise(&self, variance: f64) -> Self::Pipeline;
+    /// Slow for large sizes, see `Pipeline::average_blur`
     fn average_needle(&self) -> Filter<Self::Pipeline>;
-    fn gaussian_needle(&self, variance: f64) -> Filter<Self::Pipeline>;
+    /// Sums to one unless the generator is told otherwise, see `CpuGenerator::normalize`
+    fn gaussian_needle(&self, sigma: f64) -> Filter<Self::Pipeline>;
+    /// Uniform circular kernel of the given radius, normalized to sum to one,
+    /// for simulating defocus. Its size follows `radius`, not the generator's.
+    /// A radius that is not positive gives an empty kernel, which `validate`
+    /// reports
+    fn disk_needle(&self, radius: f64) -> Filter<Self::Pipeline>;
+    fn sobel_x(&self) -> Filter<Self::Pipeline>;
+    fn sobel_y(&self) -> Filter<Self::Pipeline>;
+    fn prewitt_x(&self) -> Filter<Self::Pipeline>;
+    fn prewitt_y(&self) -> Filter<Self::Pipeline>;
+    fn scharr_x(&self) -> Filter<Self::Pipeline>;
+    fn scharr_y(&self) -> Filter<Self::Pipeline>;
+    /// 3x3 four-neighbour Laplacian
+    fn laplacian(&self) -> Filter<Self::Pipeline>;
+    /// Laplacian of Gaussian of the generator's size, shifted to sum to zero
+    fn log_needle(&self, sigma: f64) -> Filter<Self::Pipeline>;
+    /// Gaussian of the generator's size, differentiated `order` times (up to
+    /// two) along `axis`. First derivatives have the sign of `sobel_x/y`
+    fn gaussian_derivative_needle(&self, sigma: f64, order: usize, axis: Axis) -> Filter<Self::Pipeline>;
 }
\ No newline at end of file
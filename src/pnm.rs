@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use crate::cpu::Image;
+use crate::raw::RawImage;
+use crate::PixelFormat;
+
+/// An 8-bit binary PGM or PPM file whose pixels stay on disk. Parts of it
+/// are read only when asked for, so images larger than memory can be
+/// processed tile by tile
+pub struct PnmFile {
+    file: File,
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    /// Where the pixels start in the file
+    offset: u64,
+}
+
+impl PnmFile {
+    /// Reads the header only
+    pub fn open(path: impl AsRef<Path>) -> Result<PnmFile, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(file);
+        let mut fields = vec![];
+        let mut offset = 0;
+        // Magic number, width, height and maximum value, with comments
+        // running from '#' to the end of the line
+        while fields.len() < 4 {
+            let mut line = String::new();
+            let read = reader.read_line(&mut line).map_err(|e| e.to_string())?;
+            if read == 0 {
+                return Err("Truncated PNM header".to_string());
+            }
+            offset += read as u64;
+            let content = line.split('#').next().unwrap_or("");
+            fields.extend(content.split_whitespace().map(str::to_string));
+        }
+        if fields.len() > 4 {
+            return Err("Pixel data must start on the line after the PNM header".to_string());
+        }
+        let format = match fields[0].as_str() {
+            "P5" => PixelFormat::Gray8,
+            "P6" => PixelFormat::Rgb8,
+            magic => return Err(format!("Unsupported PNM type '{magic}', only binary P5 and P6 are")),
+        };
+        let number = |n: usize| fields[n].parse::<usize>()
+            .map_err(|_| format!("Invalid PNM header field '{}'", fields[n]));
+        let (width, height, max) = (number(1)?, number(2)?, number(3)?);
+        if max != 255 {
+            return Err(format!("Only 8-bit PNM files are supported, got a maximum of {max}"));
+        }
+        Ok(PnmFile {
+            file: reader.into_inner(),
+            width,
+            height,
+            format,
+            offset,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Reads the `width`x`height` area at `(x, y)`, clipped to the image
+    pub fn read_region(&mut self, x: usize, y: usize, width: usize, height: usize) -> Result<Image, String> {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        let bytes = self.format.bytes_per_pixel();
+        let row = width * bytes;
+        let mut data = vec![0; row * height];
+        for (n, line) in data.chunks_mut(row.max(1)).enumerate() {
+            let at = self.offset + (((y + n) * self.width + x) * bytes) as u64;
+            self.file.seek(SeekFrom::Start(at))
+                .and_then(|_| self.file.read_exact(line))
+                .map_err(|e| format!("Unable to read row {}: {e}", y + n))?;
+        }
+        RawImage::new(&data, width, height, row, self.format)
+            .map(|raw| raw.to_image())
+    }
+
+    /// Reads the image tile by tile in reading order, as `(x, y, tile)`
+    pub fn tiles(&mut self, tile_w: usize, tile_h: usize) -> impl Iterator<Item = Result<(usize, usize, Image), String>> + '_ {
+        assert!(tile_w > 0 && tile_h > 0, "Tiles must not be empty");
+        let (width, height) = (self.width, self.height);
+        (0..height).step_by(tile_h)
+            .flat_map(move |y| (0..width).step_by(tile_w)
+                .map(move |x| (x, y)))
+            .map(move |(x, y)| self.read_region(x, y, tile_w, tile_h)
+                .map(|tile| (x, y, tile)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PnmFile;
+
+    #[test]
+    fn reads_regions_of_a_ppm() {
+        let path = std::env::temp_dir().join("computer_vision_pnm_test.ppm");
+        let mut data = b"P6\n# test\n3 2\n255\n".to_vec();
+        data.extend((0..18).map(|n| n as u8 * 10));
+        std::fs::write(&path, data).unwrap();
+
+        let mut pnm = PnmFile::open(&path).unwrap();
+        assert_eq!((pnm.width(), pnm.height()), (3, 2));
+        let region = pnm.read_region(1, 1, 5, 5).unwrap();
+        assert_eq!((region.width(), region.height()), (2, 1));
+        let pixel: [u8; 4] = region[(0, 0)].into();
+        assert_eq!(pixel, [120, 130, 140, 255]);
+        assert_eq!(pnm.tiles(2, 2).count(), 2);
+        std::fs::remove_file(path).unwrap();
+    }
+}
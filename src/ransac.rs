@@ -0,0 +1,213 @@
+use rand::seq::index::sample;
+use rand::{thread_rng, Rng};
+
+pub type Point = (f64, f64);
+
+/// Random sample consensus. Repeatedly fits a model to `sample_size` random
+/// points and keeps the one with the most points within `threshold` of it.
+/// The winner is refitted on all of its inliers, whose indices are returned
+/// alongside it
+pub fn ransac<P: Clone, M>(points: &[P],
+                           sample_size: usize,
+                           model_fn: impl Fn(&[P]) -> Option<M>,
+                           error_fn: impl Fn(&M, &P) -> f64,
+                           threshold: f64,
+                           iterations: usize) -> Option<(M, Vec<usize>)> {
+    ransac_with_rng(points, sample_size, model_fn, error_fn, threshold, iterations, &mut thread_rng())
+}
+
+/// `ransac` drawing its samples from `rng`, for reproducible fits
+pub fn ransac_with_rng<P: Clone, M>(points: &[P],
+                                    sample_size: usize,
+                                    model_fn: impl Fn(&[P]) -> Option<M>,
+                                    error_fn: impl Fn(&M, &P) -> f64,
+                                    threshold: f64,
+                                    iterations: usize,
+                                    rng: &mut impl Rng) -> Option<(M, Vec<usize>)> {
+    if points.len() < sample_size {
+        return None;
+    }
+    let inliers_of = |model: &M| (0..points.len())
+        .filter(|n| error_fn(model, &points[*n]) <= threshold)
+        .collect::<Vec<_>>();
+
+    let best = (0..iterations)
+        .filter_map(|_| {
+            let picked = sample(rng, points.len(), sample_size)
+                .into_iter()
+                .map(|n| points[n].clone())
+                .collect::<Vec<_>>();
+            model_fn(&picked)
+        })
+        .map(|model| inliers_of(&model))
+        .max_by_key(Vec::len)?;
+
+    let inliers = best.iter()
+        .map(|n| points[*n].clone())
+        .collect::<Vec<_>>();
+    let model = model_fn(&inliers)?;
+    let inliers = inliers_of(&model);
+    Some((model, inliers))
+}
+
+/// The line `a x + b y + c = 0`, with `(a, b)` of unit length
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Line {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+impl Line {
+    pub fn distance(&self, &(x, y): &Point) -> f64 {
+        (self.a * x + self.b * y + self.c).abs()
+    }
+}
+
+/// Total least squares line through two or more points
+pub fn fit_line(points: &[Point]) -> Option<Line> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let (mx, my) = points.iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x / n, sy + y / n));
+    let (sxx, sxy, syy) = points.iter()
+        .fold((0.0, 0.0, 0.0), |(sxx, sxy, syy), (x, y)| {
+            let (dx, dy) = (x - mx, y - my);
+            (sxx + dx * dx, sxy + dx * dy, syy + dy * dy)
+        });
+    if sxx + syy == 0.0 {
+        return None;
+    }
+    // The normal is the direction of least spread
+    let angle = 0.5 * (2.0 * sxy).atan2(sxx - syy) + std::f64::consts::FRAC_PI_2;
+    let (a, b) = (angle.cos(), angle.sin());
+    Some(Line {
+        a,
+        b,
+        c: -(a * mx + b * my)
+    })
+}
+
+pub fn ransac_line(points: &[Point], threshold: f64, iterations: usize) -> Option<(Line, Vec<usize>)> {
+    ransac(points, 2, fit_line, Line::distance, threshold, iterations)
+}
+
+/// A projective transform as a row-major 3x3 matrix
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Homography(pub [f64; 9]);
+
+impl Homography {
+    pub fn apply(&self, (x, y): Point) -> Point {
+        let h = &self.0;
+        let w = h[6] * x + h[7] * y + h[8];
+        ((h[0] * x + h[1] * y + h[2]) / w, (h[3] * x + h[4] * y + h[5]) / w)
+    }
+
+    /// Distance between where `from` is mapped to and `to`
+    pub fn transfer_error(&self, &(from, to): &(Point, Point)) -> f64 {
+        let (x, y) = self.apply(from);
+        (x - to.0).hypot(y - to.1)
+    }
+}
+
+/// Least squares homography from four or more correspondences, with the
+/// bottom right entry fixed to one
+pub fn fit_homography(pairs: &[(Point, Point)]) -> Option<Homography> {
+    if pairs.len() < 4 {
+        return None;
+    }
+    let rows = pairs.iter()
+        .flat_map(|&((x, y), (u, v))| [
+            ([x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y], u),
+            ([0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y], v),
+        ])
+        .collect::<Vec<_>>();
+
+    // Normal equations: (A^T A) h = A^T b
+    let mut ata = vec![vec![0.0; 8]; 8];
+    let mut atb = vec![0.0; 8];
+    for (row, rhs) in &rows {
+        for i in 0..8 {
+            atb[i] += row[i] * rhs;
+            for j in 0..8 {
+                ata[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    let h = solve(ata, atb)?;
+    Some(Homography([h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0]))
+}
+
+pub fn ransac_homography(pairs: &[(Point, Point)], threshold: f64, iterations: usize) -> Option<(Homography, Vec<usize>)> {
+    ransac(pairs, 4, fit_homography, Homography::transfer_error, threshold, iterations)
+}
+
+/// Gaussian elimination with partial pivoting
+pub fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let (above, below) = a.split_at_mut(row);
+            for (value, pivot) in below[0][col..].iter_mut().zip(&above[col][col..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest = (row + 1..n)
+            .map(|k| a[row][k] * x[k])
+            .sum::<f64>();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::{fit_homography, fit_line, ransac_with_rng, Homography, Line, Point};
+
+    #[test]
+    fn line_ignores_outliers() {
+        let mut points = (0..20)
+            .map(|x| (x as f64, 2.0 * x as f64 + 1.0))
+            .collect::<Vec<Point>>();
+        points.extend([(3.0, 30.0), (10.0, -5.0), (15.0, 0.0)]);
+        let (line, inliers) = ransac_with_rng(&points, 2, fit_line, Line::distance, 0.1, 50,
+                                              &mut StdRng::seed_from_u64(7)).unwrap();
+        assert_eq!(inliers, (0..20).collect::<Vec<_>>());
+        // y = 2x + 1 has the normal (2, -1) up to sign and scale
+        assert!((line.a / line.b + 2.0).abs() < 1e-9);
+        assert!(line.distance(&(100.0, 201.0)) < 1e-9);
+        assert!(fit_line(&[(1.0, 1.0), (1.0, 1.0)]).is_none());
+    }
+
+    #[test]
+    fn homography_round_trip() {
+        let truth = Homography([1.2, 0.1, 5.0, -0.2, 0.9, -3.0, 0.001, 0.002, 1.0]);
+        let mut pairs = [(0.0, 0.0), (100.0, 0.0), (0.0, 80.0), (100.0, 80.0), (40.0, 30.0), (70.0, 60.0)]
+            .map(|p| (p, truth.apply(p)))
+            .to_vec();
+        let fitted = fit_homography(&pairs).unwrap();
+        assert!(fitted.0.iter().zip(truth.0).all(|(a, b)| (a - b).abs() < 1e-9));
+
+        pairs.push(((50.0, 50.0), (0.0, 0.0)));
+        let (fitted, inliers) = ransac_with_rng(&pairs, 4, fit_homography, Homography::transfer_error, 0.5, 100,
+                                                &mut StdRng::seed_from_u64(7)).unwrap();
+        assert_eq!(inliers, (0..6).collect::<Vec<_>>());
+        assert!(fitted.transfer_error(&((20.0, 20.0), truth.apply((20.0, 20.0)))) < 1e-6);
+    }
+}
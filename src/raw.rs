@@ -0,0 +1,95 @@
+use crate::cpu::Image;
+use crate::pipeline::Image as _;
+use crate::rgba::Rgba;
+use crate::PixelFormat;
+
+/// A borrowed external pixel buffer, such as a camera frame or the rows of a
+/// GdkPixbuf. Rows are `stride` bytes apart and may be padded. Pixels are
+/// only converted when read
+#[derive(Copy, Clone, Debug)]
+pub struct RawImage<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: PixelFormat,
+}
+
+impl<'a> RawImage<'a> {
+    /// Checks that the rows fit in their stride and that `data` holds all of them
+    pub fn new(data: &'a [u8], width: usize, height: usize, stride: usize, format: PixelFormat) -> Result<RawImage<'a>, String> {
+        let row = width * format.bytes_per_pixel();
+        if stride < row {
+            return Err(format!("Stride {stride} is shorter than a row of {row} bytes"));
+        }
+        let needed = match height {
+            0 => 0,
+            _ => stride * (height - 1) + row,
+        };
+        if data.len() < needed {
+            return Err(format!("Buffer of {} bytes is too small for {width}x{height} pixels, needs {needed}", data.len()));
+        }
+        Ok(RawImage { data, width, height, stride, format })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgba {
+        let at = y * self.stride + x * self.format.bytes_per_pixel();
+        let p = &self.data[at..at + self.format.bytes_per_pixel()];
+        let rgba = match self.format {
+            PixelFormat::Rgba8 => [p[0], p[1], p[2], p[3]],
+            PixelFormat::Bgra8 => [p[2], p[1], p[0], p[3]],
+            PixelFormat::Argb8 => [p[1], p[2], p[3], p[0]],
+            PixelFormat::Rgb8 => [p[0], p[1], p[2], 255],
+            PixelFormat::Gray8 => [p[0], p[0], p[0], 255],
+        };
+        Rgba::from(&rgba)
+    }
+
+    pub fn to_image(&self) -> Image {
+        Image::from_fn(self.width, self.height, |x, y| self.pixel(x, y))
+    }
+}
+
+impl Image {
+    /// Imports an external buffer directly, see `RawImage`
+    pub fn from_raw_parts(data: &[u8], width: usize, height: usize, stride: usize, format: PixelFormat) -> Result<Image, String> {
+        RawImage::new(data, width, height, stride, format)
+            .map(|raw| raw.to_image())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rgba::Rgba;
+    use crate::PixelFormat;
+    use super::RawImage;
+
+    #[test]
+    fn strided_rows_and_channel_orders() {
+        // Two pixels per row, each row padded with two junk bytes
+        let bgra = [10, 20, 30, 40, 11, 21, 31, 41, 99, 99,
+                    12, 22, 32, 42, 13, 23, 33, 43, 99, 99];
+        let raw = RawImage::new(&bgra, 2, 2, 10, PixelFormat::Bgra8).unwrap();
+        let channels = |pixel: Rgba| -> [f64; 4] { pixel.into() };
+        assert_eq!(channels(raw.pixel(1, 1)), channels(Rgba::from(&[33, 23, 13, 43])));
+        assert_eq!(channels(raw.pixel(0, 0)), channels(Rgba::from(&[30, 20, 10, 40])));
+        assert_eq!((raw.to_image().width(), raw.to_image().height()), (2, 2));
+
+        let argb = [40, 10, 20, 30];
+        let raw = RawImage::new(&argb, 1, 1, 4, PixelFormat::Argb8).unwrap();
+        assert_eq!(channels(raw.pixel(0, 0)), channels(Rgba::from(&[10, 20, 30, 40])));
+
+        assert!(RawImage::new(&bgra, 2, 2, 6, PixelFormat::Bgra8).is_err());
+        // The last row needs no padding after it
+        assert!(RawImage::new(&bgra[..18], 2, 2, 10, PixelFormat::Bgra8).is_ok());
+        assert!(RawImage::new(&bgra[..17], 2, 2, 10, PixelFormat::Bgra8).is_err());
+    }
+}
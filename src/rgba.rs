@@ -91,6 +91,23 @@ impl Rgba {
             a: 1.0
         }
     }
+
+    /// An opaque colour from hue in degrees, and saturation and value in [0, 1]
+    pub fn from_hsv(hue: f64, saturation: f64, value: f64) -> Self {
+        let chroma = value * saturation;
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as usize {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        (r + m, g + m, b + m, 1.0).into()
+    }
 }
 
 impl Rgba {
@@ -114,6 +131,16 @@ impl Rgba {
         }
     }
 
+    /// Like `map`, but leaves the alpha channel untouched
+    pub fn map_color(self, f: impl Fn(f64) -> f64) -> Rgba {
+        Rgba {
+            r: f(self.r),
+            b: f(self.b),
+            g: f(self.g),
+            a: self.a
+        }
+    }
+
     pub fn min(self, other: Self) -> Self {
         self.into_iter()
             .zip(other)
@@ -128,6 +155,46 @@ impl Rgba {
             .collect()
     }
 
+    /// Multiplies the colour channels by a 3x3 matrix, keeping alpha
+    pub fn transform(self, matrix: [[f64; 3]; 3]) -> Self {
+        let [r, g, b] = matrix.map(|[mr, mg, mb]| mr * self.r + mg * self.g + mb * self.b);
+        Rgba { r, g, b, a: self.a }
+    }
+
+    /// Converts sRGB-encoded channels to linear light
+    pub fn linearize(self) -> Self {
+        self.map_color(|c| if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        })
+    }
+
+    /// Converts linear light channels back to sRGB encoding
+    pub fn delinearize(self) -> Self {
+        self.map_color(|c| if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        })
+    }
+
+    /// Per-channel median of a set of pixels
+    pub fn median(pixels: &[Rgba]) -> Rgba {
+        let mut channels: [Vec<f64>; 4] = Default::default();
+        for pixel in pixels {
+            for (channel, value) in channels.iter_mut().zip(pixel) {
+                channel.push(value);
+            }
+        }
+        channels.into_iter()
+            .map(|mut channel| {
+                channel.sort_by(f64::total_cmp);
+                channel[channel.len() / 2]
+            })
+            .collect()
+    }
+
     pub fn grayscale(self) -> Self {
         let Rgba {r, g, b, a} = self * Self::GRAYSCALE_FACTOR;
         Rgba::gray((r + g + b) / 3.0)
@@ -137,6 +204,23 @@ impl Rgba {
     pub fn alpha(&self) -> f64 {
         self.a
     }
+
+    /// Unweighted mean of the colour channels
+    pub fn intensity(&self) -> f64 {
+        (self.r + self.g + self.b) / 3.0
+    }
+
+    pub fn red(&self) -> f64 {
+        self.r
+    }
+
+    pub fn green(&self) -> f64 {
+        self.g
+    }
+
+    pub fn blue(&self) -> f64 {
+        self.b
+    }
 }
 
 impl std::ops::Mul for Rgba {
@@ -183,6 +267,16 @@ impl std::ops::Div<f64> for Rgba {
     }
 }
 
+impl std::ops::Mul<f64> for Rgba {
+    type Output = Rgba;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        self.into_iter()
+            .map(|a| a * rhs)
+            .collect()
+    }
+}
+
 impl From<(f64, f64, f64, f64)> for Rgba {
     fn from((r, g, b, a): (f64, f64, f64, f64)) -> Self {
         Rgba {
@@ -0,0 +1,96 @@
+use crate::cpu::{gaussian_derivative_weights, Image};
+use crate::rgba::Rgba;
+use crate::{Anchor, BorderMode, ReduceOp};
+
+/// Responses to a few gaussian derivative filters, from which the response
+/// of the same derivative at any orientation is a weighted sum
+pub struct SteerableBasis {
+    order: usize,
+    /// `[Gx, Gy]` for the first order, `[Gxx, Gxy, Gyy]` for the second
+    responses: Vec<Image>,
+}
+
+impl SteerableBasis {
+    /// Filters `image` with the x and y derivatives of a gaussian
+    pub fn first_order(image: &Image, sigma: f64) -> SteerableBasis {
+        SteerableBasis {
+            order: 1,
+            responses: vec![respond(image, sigma, 1, 0), respond(image, sigma, 0, 1)],
+        }
+    }
+
+    /// Filters `image` with the second derivatives of a gaussian
+    pub fn second_order(image: &Image, sigma: f64) -> SteerableBasis {
+        SteerableBasis {
+            order: 2,
+            responses: vec![respond(image, sigma, 2, 0), respond(image, sigma, 1, 1), respond(image, sigma, 0, 2)],
+        }
+    }
+
+    /// Weights of the basis responses for the derivative along `angle`,
+    /// in radians from the x axis
+    fn weights(&self, angle: f64) -> Vec<f64> {
+        let (c, s) = (angle.cos(), angle.sin());
+        match self.order {
+            1 => vec![c, s],
+            _ => vec![c * c, 2.0 * c * s, s * s],
+        }
+    }
+
+    /// The filter response along `angle`, in radians from the x axis
+    pub fn response(&self, angle: f64) -> Image {
+        let weights = self.weights(angle);
+        self.responses[0].similar(|x, y| self.responses.iter()
+            .zip(&weights)
+            .map(|(response, weight)| response[(x, y)] * *weight)
+            .fold(Rgba::gray(0.0), std::ops::Add::add)
+            .with_alpha(1.0))
+    }
+
+    /// The angle with the strongest response at `(x, y)`, by intensity
+    pub fn dominant_angle(&self, x: usize, y: usize) -> f64 {
+        let at = |n: usize| self.responses[n][(x, y)].intensity();
+        match self.order {
+            1 => at(1).atan2(at(0)),
+            _ => 0.5 * (2.0 * at(1)).atan2(at(0) - at(2)),
+        }
+    }
+}
+
+fn respond(image: &Image, sigma: f64, x_order: usize, y_order: usize) -> Image {
+    let size = 2 * (3.0 * sigma).ceil() as usize + 1;
+    let weights = gaussian_derivative_weights(size, sigma, x_order, y_order);
+    let kernel = Image::from_weights(&weights);
+    image.convolve(&kernel, Anchor::Center, BorderMode::Replicate, ReduceOp::Sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_6};
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::SteerableBasis;
+
+    #[test]
+    fn steers_to_the_gradient_of_a_ramp() {
+        let (c, s) = (FRAC_PI_6.cos(), FRAC_PI_6.sin());
+        let ramp = Image::from_fn(21, 21, |x, y| Rgba::gray(0.2 + 0.02 * (c * x as f64 + s * y as f64)));
+        let basis = SteerableBasis::first_order(&ramp, 1.5);
+        assert!((basis.dominant_angle(10, 10) - FRAC_PI_6).abs() < 1e-6);
+        assert!((basis.response(FRAC_PI_6)[(10, 10)].red() - 0.02).abs() < 1e-3);
+        assert!(basis.response(FRAC_PI_6 + FRAC_PI_2)[(10, 10)].red().abs() < 1e-9);
+
+        // A parabola across the same direction curves only along it
+        let valley = Image::from_fn(21, 21, |x, y| {
+            let along = c * (x as f64 - 10.0) + s * (y as f64 - 10.0);
+            Rgba::gray(0.1 + 0.001 * along * along)
+        });
+        let basis = SteerableBasis::second_order(&valley, 1.5);
+        let (along, across) = (basis.response(FRAC_PI_6)[(10, 10)].red(),
+                               basis.response(FRAC_PI_6 + FRAC_PI_2)[(10, 10)].red());
+        assert!((basis.dominant_angle(10, 10) - FRAC_PI_6).abs() < 0.01);
+        assert!((along - 0.002).abs() < 0.0002);
+        assert!(across.abs() < 0.1 * along);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::cpu::Image;
+
+/// Gray levels intensities are quantized to by `glcm`
+pub const GLCM_LEVELS: usize = 16;
+
+/// Gray-level co-occurrence matrix: how often a pixel of one gray level
+/// lies at a fixed offset from a pixel of another. It is symmetric and
+/// normalized to sum to one
+#[derive(Clone, Debug)]
+pub struct Glcm {
+    /// Indexed by the two gray levels
+    pub matrix: Vec<Vec<f64>>,
+}
+
+/// The co-occurrence of `GLCM_LEVELS` gray levels `distance` pixels apart
+/// along `angle`, in radians counterclockwise from the x axis
+pub fn glcm(image: &Image, distance: usize, angle: f64) -> Glcm {
+    Glcm::new(image, distance, angle, GLCM_LEVELS)
+}
+
+impl Glcm {
+    pub fn new(image: &Image, distance: usize, angle: f64, levels: usize) -> Glcm {
+        assert!(levels > 0, "A co-occurrence matrix needs at least one level");
+        let dx = (distance as f64 * angle.cos()).round() as i64;
+        // Image rows go down, so a positive angle moves up
+        let dy = -(distance as f64 * angle.sin()).round() as i64;
+        let level = |x: usize, y: usize| ((image[(x, y)].intensity().clamp(0.0, 1.0) * levels as f64) as usize)
+            .min(levels - 1);
+
+        let mut matrix = vec![vec![0.0; levels]; levels];
+        let mut pairs = 0.0;
+        for x in 0..image.width() {
+            for y in 0..image.height() {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx < 0 || ny < 0 || nx >= image.width() as i64 || ny >= image.height() as i64 {
+                    continue;
+                }
+                let (a, b) = (level(x, y), level(nx as usize, ny as usize));
+                matrix[a][b] += 1.0;
+                matrix[b][a] += 1.0;
+                pairs += 2.0;
+            }
+        }
+        if pairs > 0.0 {
+            matrix.iter_mut()
+                .flatten()
+                .for_each(|p| *p /= pairs);
+        }
+        Glcm { matrix }
+    }
+
+    pub fn levels(&self) -> usize {
+        self.matrix.len()
+    }
+
+    fn sum(&self, f: impl Fn(f64, f64, f64) -> f64) -> f64 {
+        self.matrix.iter()
+            .enumerate()
+            .flat_map(|(i, row)| row.iter()
+                .enumerate()
+                .map(move |(j, p)| (i as f64, j as f64, *p)))
+            .map(|(i, j, p)| f(i, j, p))
+            .sum()
+    }
+
+    /// Local variation, high for coarse changes between neighbours
+    pub fn contrast(&self) -> f64 {
+        self.sum(|i, j, p| p * (i - j).powi(2))
+    }
+
+    /// Closeness to the diagonal, `1` for a flat image
+    pub fn homogeneity(&self) -> f64 {
+        self.sum(|i, j, p| p / (1.0 + (i - j).powi(2)))
+    }
+
+    /// Square root of the angular second moment, `1` for a flat image
+    pub fn energy(&self) -> f64 {
+        self.sum(|_, _, p| p * p).sqrt()
+    }
+
+    /// Linear dependency between neighbouring gray levels, in [-1, 1].
+    /// `1` when the image has a single gray level
+    pub fn correlation(&self) -> f64 {
+        // Symmetric, so both marginals are the same
+        let mean = self.sum(|i, _, p| i * p);
+        let variance = self.sum(|i, _, p| p * (i - mean).powi(2));
+        if variance == 0.0 {
+            return 1.0;
+        }
+        self.sum(|i, j, p| p * (i - mean) * (j - mean)) / variance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+    use crate::cpu::Image;
+    use crate::pipeline::Image as _;
+    use crate::rgba::Rgba;
+    use super::glcm;
+
+    #[test]
+    fn stripes_vary_across_but_not_along() {
+        let stripes = Image::from_fn(16, 16, |x, _| Rgba::gray((x % 2) as f64));
+        let across = glcm(&stripes, 1, 0.0);
+        let along = glcm(&stripes, 1, FRAC_PI_2);
+        assert_eq!(across.contrast(), 225.0);
+        assert_eq!(across.correlation(), -1.0);
+        assert_eq!(along.contrast(), 0.0);
+        assert_eq!(along.homogeneity(), 1.0);
+        assert_eq!(along.correlation(), 1.0);
+    }
+}
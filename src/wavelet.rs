@@ -0,0 +1,106 @@
+use std::f64::consts::FRAC_1_SQRT_2;
+use crate::Shrinkage;
+
+/// One level of the orthonormal Haar transform: averages in the first half
+/// of `data`, details in the second. The length must be even
+fn haar_step(data: &mut [f64]) {
+    let half = data.len() / 2;
+    let out = (0..half)
+        .map(|i| (data[2 * i] + data[2 * i + 1]) * FRAC_1_SQRT_2)
+        .chain((0..half)
+            .map(|i| (data[2 * i] - data[2 * i + 1]) * FRAC_1_SQRT_2))
+        .collect::<Vec<_>>();
+    data.copy_from_slice(&out);
+}
+
+fn inverse_haar_step(data: &mut [f64]) {
+    let half = data.len() / 2;
+    let out = (0..half)
+        .flat_map(|i| {
+            let (a, d) = (data[i], data[half + i]);
+            [(a + d) * FRAC_1_SQRT_2, (a - d) * FRAC_1_SQRT_2]
+        })
+        .collect::<Vec<_>>();
+    data.copy_from_slice(&out);
+}
+
+/// Applies `step` to every column, then every row, of the top left
+/// `width` by `height` corner of a grid indexed as `[x][y]`
+fn each_line(data: &mut [Vec<f64>], width: usize, height: usize, step: fn(&mut [f64])) {
+    for column in &mut data[..width] {
+        step(&mut column[..height]);
+    }
+    let mut row = vec![0.0; width];
+    for y in 0..height {
+        for (value, column) in row.iter_mut().zip(data.iter()) {
+            *value = column[y];
+        }
+        step(&mut row);
+        for (value, column) in row.iter().zip(data.iter_mut()) {
+            column[y] = *value;
+        }
+    }
+}
+
+/// In-place 2D Haar transform over `levels` levels, each halving the
+/// average band in the top left corner. Both dimensions of the grid, indexed
+/// as `[x][y]`, must be multiples of `2^levels`
+pub fn haar2d(data: &mut [Vec<f64>], levels: usize) {
+    let (width, height) = (data.len(), data.first().map_or(0, Vec::len));
+    for level in 0..levels {
+        each_line(data, width >> level, height >> level, haar_step);
+    }
+}
+
+/// Undoes `haar2d` with the same number of levels
+pub fn inverse_haar2d(data: &mut [Vec<f64>], levels: usize) {
+    let (width, height) = (data.len(), data.first().map_or(0, Vec::len));
+    for level in (0..levels).rev() {
+        // Columns then rows again: the two passes commute
+        each_line(data, width >> level, height >> level, inverse_haar_step);
+    }
+}
+
+/// Shrinks every detail coefficient of a `levels` deep transform towards
+/// zero by `threshold`, leaving the coarsest averages alone
+pub fn shrink(data: &mut [Vec<f64>], levels: usize, threshold: f64, shrinkage: Shrinkage) {
+    let (width, height) = (data.len(), data.first().map_or(0, Vec::len));
+    let (average_width, average_height) = (width >> levels, height >> levels);
+    for (x, column) in data.iter_mut().enumerate() {
+        for (y, c) in column.iter_mut().enumerate() {
+            if x < average_width && y < average_height {
+                continue;
+            }
+            *c = match shrinkage {
+                Shrinkage::Hard if c.abs() < threshold => 0.0,
+                Shrinkage::Hard => *c,
+                Shrinkage::Soft => c.signum() * (c.abs() - threshold).max(0.0),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Shrinkage;
+    use super::{haar2d, inverse_haar2d, shrink};
+
+    #[test]
+    fn shrinking_drops_small_details_only() {
+        let image = (0..8)
+            .map(|x| (0..4)
+                .map(|y| if x < 4 { 0.2 } else { 0.8 } + if (x + y) % 2 == 0 { 0.01 } else { 0.0 })
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut data = image.clone();
+        haar2d(&mut data, 2);
+        let mut round_trip = data.clone();
+        inverse_haar2d(&mut round_trip, 2);
+        assert!(round_trip.iter().flatten().zip(image.iter().flatten()).all(|(a, b)| (a - b).abs() < 1e-12));
+
+        shrink(&mut data, 2, 0.05, Shrinkage::Hard);
+        inverse_haar2d(&mut data, 2);
+        assert!((data[1][1] - 0.205).abs() < 1e-9);
+        assert!((data[6][2] - 0.805).abs() < 1e-9);
+    }
+}